@@ -0,0 +1,34 @@
+//! Exponential backoff shared by HTTP and process retries
+
+use std::time::Duration;
+
+/// Maximum retry delay cap (10 seconds)
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// How long to wait before retry number `attempt` (starting at 1): `base` doubled for
+/// every retry after the first, capped at [`MAX_RETRY_DELAY`]
+pub(crate) fn retry_delay(base: Duration, attempt: u32) -> Duration {
+    base.checked_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+        .unwrap_or(MAX_RETRY_DELAY)
+        .min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let base = Duration::from_millis(500);
+        assert_eq!(retry_delay(base, 1), base);
+        assert_eq!(retry_delay(base, 3), Duration::from_secs(2));
+        assert_eq!(retry_delay(base, 10), MAX_RETRY_DELAY);
+        // Large delays are capped instead of overflowing
+        assert_eq!(
+            retry_delay(Duration::from_secs(u64::MAX / 4), 40),
+            MAX_RETRY_DELAY
+        );
+    }
+}
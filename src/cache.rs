@@ -51,6 +51,9 @@ struct CacheEntry<T> {
     version: String,
     /// Hash of watched files at creation time
     files_hash: Option<String>,
+    /// Per-entry freshness duration in seconds, checked in addition to the manager's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fresh_duration: Option<u64>,
 }
 
 /// Builder for CacheManager
@@ -157,6 +160,7 @@ impl CacheManager {
             entry.created_at,
             &entry.version,
             entry.files_hash.as_deref(),
+            entry.fresh_duration,
         ) {
             return None;
         }
@@ -165,8 +169,54 @@ impl CacheManager {
         Some(entry.data)
     }
 
+    /// Get a value from the cache even if it is no longer fresh
+    ///
+    /// Only the version key is checked; expired entries and entries whose watched
+    /// files changed are still returned. This is useful for revalidating or falling
+    /// back to old data when recomputing it fails.
+    pub fn get_stale<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let path = self.cache_path(key);
+
+        if !path.exists() {
+            return None;
+        }
+
+        let content = file::read_to_string(&path).ok()?;
+        let entry: CacheEntry<T> = serde_json::from_str(&content).ok()?;
+
+        if entry.version != self.version {
+            trace!("Cache miss (version mismatch): {}", key);
+            return None;
+        }
+
+        trace!("Cache hit (stale allowed): {}", key);
+        Some(entry.data)
+    }
+
     /// Store a value in the cache
     pub fn set<T: Serialize>(&self, key: &str, data: &T) -> XXResult<()> {
+        self.write_entry(key, data, None)
+    }
+
+    /// Store a value in the cache with its own freshness duration
+    ///
+    /// The entry is considered stale once `duration` has elapsed, even if the
+    /// manager's `fresh_duration` is longer or unset.
+    pub fn set_with_fresh_duration<T: Serialize>(
+        &self,
+        key: &str,
+        data: &T,
+        duration: Duration,
+    ) -> XXResult<()> {
+        self.write_entry(key, data, Some(duration.as_secs()))
+    }
+
+    fn write_entry<T: Serialize>(
+        &self,
+        key: &str,
+        data: &T,
+        fresh_duration: Option<u64>,
+    ) -> XXResult<()> {
         let path = self.cache_path(key);
 
         let entry = CacheEntry {
@@ -177,6 +227,7 @@ impl CacheManager {
                 .as_secs(),
             version: self.version.clone(),
             files_hash: self.compute_files_hash(),
+            fresh_duration,
         };
 
         let content = serde_json::to_string_pretty(&entry)
@@ -232,6 +283,7 @@ impl CacheManager {
                     entry.created_at,
                     &entry.version,
                     entry.files_hash.as_deref(),
+                    entry.fresh_duration,
                 );
             }
         }
@@ -252,6 +304,7 @@ impl CacheManager {
         created_at: u64,
         version: &str,
         files_hash: Option<&str>,
+        entry_fresh_duration: Option<u64>,
     ) -> bool {
         // Check version
         if version != self.version {
@@ -259,15 +312,22 @@ impl CacheManager {
             return false;
         }
 
-        // Check freshness duration
-        if let Some(duration) = self.fresh_duration {
+        // Check freshness duration, using the shorter of the manager's and the entry's
+        let fresh_secs = match (
+            self.fresh_duration.map(|d| d.as_secs()),
+            entry_fresh_duration,
+        ) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if let Some(fresh_secs) = fresh_secs {
             let now = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs();
 
             // Use saturating_sub to handle clock adjustments gracefully
-            if now.saturating_sub(created_at) >= duration.as_secs() {
+            if now.saturating_sub(created_at) >= fresh_secs {
                 trace!("Cache miss (expired): {}", key);
                 return false;
             }
@@ -433,4 +493,49 @@ mod tests {
         let retrieved: Option<TestData> = cache.get("complex");
         assert_eq!(retrieved, Some(data));
     }
+
+    #[test]
+    fn test_cache_entry_fresh_duration() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let cache = CacheManager::builder()
+            .cache_dir(tmpdir.path())
+            .version("1.0")
+            .build()
+            .unwrap();
+
+        cache
+            .set_with_fresh_duration("short", &"value".to_string(), Duration::from_secs(0))
+            .unwrap();
+        cache
+            .set_with_fresh_duration("long", &"value".to_string(), Duration::from_secs(3600))
+            .unwrap();
+
+        assert!(cache.get::<String>("short").is_none());
+        assert!(!cache.contains("short"));
+        assert_eq!(cache.get::<String>("long"), Some("value".to_string()));
+
+        // Stale entries are still available when explicitly requested
+        assert_eq!(
+            cache.get_stale::<String>("short"),
+            Some("value".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cache_get_stale_version_mismatch() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let cache_v1 = CacheManager::builder()
+            .cache_dir(tmpdir.path())
+            .version("1.0")
+            .build()
+            .unwrap();
+        cache_v1.set("key", &"value".to_string()).unwrap();
+
+        let cache_v2 = CacheManager::builder()
+            .cache_dir(tmpdir.path())
+            .version("2.0")
+            .build()
+            .unwrap();
+        assert!(cache_v2.get_stale::<String>("key").is_none());
+    }
 }
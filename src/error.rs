@@ -8,6 +8,8 @@
 //! - `FileError` - File operations with path context
 //! - `GitError` - Git operations with repository path
 //! - `ProcessError` - Process execution with command context
+//! - `ProcessFailed` - A process exited unsuccessfully, with the end of its output
+//! - `ProcessTimeout` / `ProcessCancelled` - A process stopped by a timeout or cancel handle
 //! - Additional feature-specific errors when features are enabled
 //!
 //! ## Usage
@@ -22,6 +24,7 @@
 //! }
 //! ```
 
+use std::fmt;
 use std::path::PathBuf;
 
 use miette::Diagnostic;
@@ -45,6 +48,18 @@ pub enum XXError {
     #[diagnostic(code(xx::process), url(docsrs))]
     ProcessError(std::io::Error, String),
 
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    ProcessFailed(Box<ProcessFailure>),
+
+    #[error("timed out after {1:?}\n{0}")]
+    #[diagnostic(code(xx::process::timeout), url(docsrs))]
+    ProcessTimeout(String, std::time::Duration),
+
+    #[error("cancelled\n{0}")]
+    #[diagnostic(code(xx::process::cancelled), url(docsrs))]
+    ProcessCancelled(String),
+
     #[cfg(any(
         feature = "archive_untar_gzip",
         feature = "archive_untar_bzip2",
@@ -82,6 +97,63 @@ pub enum XXError {
     FSLockError(fslock::Error, String),
 }
 
+/// A process that exited unsuccessfully
+///
+/// Carried by [`XXError::ProcessFailed`]. The output fields hold the last lines of
+/// whatever was captured or streamed to line handlers; output that went straight to
+/// the terminal isn't included. As a `miette` diagnostic, the output is shown as help
+/// text below the error.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessFailure {
+    /// The command that was run
+    pub command: String,
+    /// Exit code, if the process exited normally
+    pub code: Option<i32>,
+    /// Signal that terminated the process (Unix only)
+    pub signal: Option<i32>,
+    /// The end of the process's stdout
+    pub stdout: String,
+    /// The end of the process's stderr
+    pub stderr: String,
+    /// The whole pipeline, when the command was one stage of a pipeline
+    pub pipeline: Option<String>,
+}
+
+impl fmt::Display for ProcessFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exited with code {code}")?,
+            (None, Some(signal)) => write!(f, "terminated by signal {signal}")?,
+            (None, None) => write!(f, "terminated by signal")?,
+        }
+        write!(f, "\n{}", self.command)?;
+        if let Some(pipeline) = &self.pipeline {
+            write!(f, "\nin pipeline: {pipeline}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProcessFailure {}
+
+impl Diagnostic for ProcessFailure {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new("xx::process::failed"))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let sections: Vec<String> = [("stdout", &self.stdout), ("stderr", &self.stderr)]
+            .into_iter()
+            .filter(|(_, output)| !output.trim().is_empty())
+            .map(|(name, output)| format!("{name}:\n{}", output.trim_end()))
+            .collect();
+        match sections.is_empty() {
+            true => None,
+            false => Some(Box::new(sections.join("\n"))),
+        }
+    }
+}
+
 /// A specialized Result type for xx operations
 ///
 /// This type alias is used throughout the xx library for functions that may return an error.
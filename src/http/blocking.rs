@@ -0,0 +1,574 @@
+//! Blocking HTTP client
+//!
+//! A synchronous wrapper around [`http::Client`](crate::http::Client) for code that isn't
+//! running inside an async runtime. Requests are driven on a shared background tokio
+//! runtime, so retries, authentication, proxies and headers behave exactly like the
+//! async client.
+//!
+//! These functions must not be called from within an async runtime; they return an
+//! error instead of blocking the executor. Use [`xx::http`](super) there.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use xx::http::blocking;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! // Simple GET request
+//! let resp = blocking::get("https://httpbin.org/get")?;
+//! println!("Status: {}", resp.status);
+//!
+//! // GET with options
+//! let resp = blocking::Client::new()
+//!     .timeout(std::time::Duration::from_secs(30))
+//!     .retries(3)
+//!     .bearer_token("my-token")
+//!     .get("https://api.example.com/protected")?;
+//!
+//! // Download a file
+//! blocking::download("https://example.com/file.zip", "/tmp/file.zip")?;
+//!
+//! // Configure the async client and use it synchronously
+//! let client = blocking::Client::from(xx::http::Client::new().netrc(true));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::IntoUrl;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::multipart::Form;
+use super::{Auth, Body, FixtureMode, TransferProgress, XXHTTPResponse};
+use crate::{XXResult, error};
+
+/// Run a future to completion on the shared blocking runtime
+fn block_on<F: Future>(future: F) -> XXResult<F::Output> {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(error!(
+            "xx::http::blocking cannot be used from within an async runtime, use xx::http instead"
+        ));
+    }
+    let runtime = match RUNTIME.get() {
+        Some(runtime) => runtime,
+        None => {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("xx-http-blocking")
+                .enable_all()
+                .build()
+                .map_err(|err| error!("Failed to start HTTP runtime: {}", err))?;
+            RUNTIME.get_or_init(|| runtime)
+        }
+    };
+    Ok(runtime.block_on(future))
+}
+
+/// Generate builder methods that forward to the async client
+macro_rules! forward_builder {
+    ($($(#[$attr:meta])* fn $name:ident $(<$($g:ident: $bound:path),*>)?($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name $(<$($g: $bound),*>)? (self, $($arg: $ty),*) -> Self {
+                Self {
+                    inner: self.inner.$name($($arg),*),
+                }
+            }
+        )*
+    };
+}
+
+/// Blocking HTTP client with configurable options
+///
+/// Mirrors [`http::Client`](super::Client); see it for details on each option.
+#[derive(Default)]
+pub struct Client {
+    inner: super::Client,
+}
+
+impl From<super::Client> for Client {
+    fn from(inner: super::Client) -> Self {
+        Self { inner }
+    }
+}
+
+impl Client {
+    /// Create a new blocking HTTP client with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    forward_builder! {
+        /// Set the request timeout
+        fn timeout(timeout: Duration);
+        /// Set the number of retries for failed requests
+        fn retries(retries: u32);
+        /// Set the base delay between retries (uses exponential backoff)
+        fn retry_delay(delay: Duration);
+        /// Set a custom user agent
+        fn user_agent<S: Into<String>>(agent: S);
+        /// Add a custom header to the request
+        fn header<K: Into<String>, V: Into<String>>(key: K, value: V);
+        /// Set HTTP Basic authentication
+        fn basic_auth<U: Into<String>, P: Into<String>>(username: U, password: P);
+        /// Set Bearer token authentication
+        fn bearer_token<T: Into<String>>(token: T);
+        /// Look up credentials for each host in a netrc file
+        fn netrc(enabled: bool);
+        /// Look up credentials for each host in a specific netrc file
+        fn netrc_file<P: AsRef<Path>>(path: P);
+        /// Send the token in an environment variable as a bearer token to one host
+        fn token_env<V: Into<String>, H: Into<String>>(var: V, host: H);
+        /// Send all requests through a proxy
+        fn proxy<S: Into<String>>(url: S);
+        /// Send `http://` requests through a proxy
+        fn http_proxy<S: Into<String>>(url: S);
+        /// Send `https://` requests through a proxy
+        fn https_proxy<S: Into<String>>(url: S);
+        /// Authenticate to the proxy with HTTP Basic credentials
+        fn proxy_auth<U: Into<String>, P: Into<String>>(username: U, password: P);
+        /// Bypass the proxy for some hosts
+        fn no_proxy<S: Into<String>>(hosts: S);
+        /// Whether to read proxy settings from the environment (default: true)
+        fn env_proxy(enabled: bool);
+        /// Set the maximum number of redirects to follow (default: 10)
+        fn max_redirects(max: usize);
+        /// Whether to follow redirects (default: true)
+        fn follow_redirects(enabled: bool);
+        /// Refuse to make network requests
+        fn offline(enabled: bool);
+        /// Serve responses from recorded fixtures in a directory
+        fn fixtures<P: AsRef<Path>>(dir: P);
+        /// Set the fixture mode instead of reading it from `XX_HTTP_FIXTURES`
+        fn fixture_mode(mode: FixtureMode);
+    }
+
+    #[cfg(any(
+        feature = "native-tls",
+        feature = "rustls",
+        feature = "rustls-native-roots"
+    ))]
+    forward_builder! {
+        /// Trust additional root certificates in PEM format (requires a TLS feature)
+        fn root_certificate_pem<B: Into<Vec<u8>>>(pem: B);
+        /// Trust an additional root certificate in DER format (requires a TLS feature)
+        fn root_certificate_der<B: Into<Vec<u8>>>(der: B);
+        /// Trust the root certificates in a PEM bundle or DER file (requires a TLS feature)
+        fn root_certificate_file<P: AsRef<Path>>(path: P);
+        /// Trust the certificates in the file named by an environment variable (requires a TLS feature)
+        fn root_certificates_from_env<S: Into<String>>(var: S);
+        /// Whether to trust the system/built-in root certificates (default: true)
+        fn built_in_root_certs(enabled: bool);
+        /// Present a client certificate for mutual TLS (requires a TLS feature)
+        fn identity_pem<B: Into<Vec<u8>>>(pem: B);
+    }
+
+    #[cfg(feature = "cache")]
+    forward_builder! {
+        /// Cache GET responses on disk (requires `cache` feature)
+        fn cache(cache: crate::cache::CacheManager);
+        /// Serve stale cached responses when the server can't be reached (requires `cache` feature)
+        fn stale_on_error(enabled: bool);
+    }
+
+    /// Add multiple headers at once
+    pub fn headers<I, K, V>(self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            inner: self.inner.headers(headers),
+        }
+    }
+
+    /// Add a middleware that sees every request and response
+    pub fn middleware<M: super::Middleware + 'static>(self, middleware: M) -> Self {
+        Self {
+            inner: self.inner.middleware(middleware),
+        }
+    }
+
+    /// Resolve credentials for each request with a callback
+    pub fn credential_provider<F>(self, provider: F) -> Self
+    where
+        F: Fn(&reqwest::Url) -> Option<Auth> + Send + Sync + 'static,
+    {
+        Self {
+            inner: self.inner.credential_provider(provider),
+        }
+    }
+
+    /// Only follow redirects to these hosts
+    pub fn redirect_hosts<I, S>(self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            inner: self.inner.redirect_hosts(hosts),
+        }
+    }
+
+    /// Perform a GET request
+    pub fn get(&self, url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.get(url))?
+    }
+
+    /// Iterate over the pages of a paginated API starting at `url`
+    pub fn paginate(&self, url: impl IntoUrl) -> Paginator<'_> {
+        Paginator {
+            inner: self.inner.paginate(url),
+        }
+    }
+
+    /// Perform a GET request and return bytes
+    pub fn get_bytes(&self, url: impl IntoUrl) -> XXResult<Vec<u8>> {
+        block_on(self.inner.get_bytes(url))?
+    }
+
+    /// Perform a GET request and return bytes, reporting progress as the body arrives
+    pub fn get_bytes_with_progress<F>(&self, url: impl IntoUrl, on_progress: F) -> XXResult<Vec<u8>>
+    where
+        F: Fn(&TransferProgress) + Send + Sync,
+    {
+        block_on(self.inner.get_bytes_with_progress(url, on_progress))?
+    }
+
+    /// Download a file
+    pub fn download(&self, url: impl IntoUrl, to: impl AsRef<Path>) -> XXResult<()> {
+        block_on(self.inner.download(url, to))?
+    }
+
+    /// Download a file, reporting progress as the body arrives
+    pub fn download_with_progress<F>(
+        &self,
+        url: impl IntoUrl,
+        to: impl AsRef<Path>,
+        on_progress: F,
+    ) -> XXResult<()>
+    where
+        F: Fn(&TransferProgress) + Send + Sync,
+    {
+        block_on(self.inner.download_with_progress(url, to, on_progress))?
+    }
+
+    /// Perform a POST request with a JSON body
+    pub fn post_json<T: Serialize + ?Sized>(
+        &self,
+        url: impl IntoUrl,
+        body: &T,
+    ) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.post_json(url, body))?
+    }
+
+    /// Perform a POST request with a raw text or binary body
+    pub fn post(&self, url: impl IntoUrl, body: impl Into<Body>) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.post(url, body))?
+    }
+
+    /// Perform a POST request with no body
+    pub fn post_empty(&self, url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.post_empty(url))?
+    }
+
+    /// Perform a POST request with form data
+    pub fn post_form<T: Serialize + ?Sized>(
+        &self,
+        url: impl IntoUrl,
+        form: &T,
+    ) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.post_form(url, form))?
+    }
+
+    /// Perform a POST request with a multipart/form-data body
+    pub fn post_multipart(&self, url: impl IntoUrl, form: Form) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.post_multipart(url, form))?
+    }
+
+    /// Perform a POST request that streams a file from disk as the body
+    pub fn post_file(&self, url: impl IntoUrl, path: impl AsRef<Path>) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.post_file(url, path))?
+    }
+
+    /// Perform a PUT request with a JSON body
+    pub fn put_json<T: Serialize + ?Sized>(
+        &self,
+        url: impl IntoUrl,
+        body: &T,
+    ) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.put_json(url, body))?
+    }
+
+    /// Perform a PUT request with a raw text or binary body
+    pub fn put(&self, url: impl IntoUrl, body: impl Into<Body>) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.put(url, body))?
+    }
+
+    /// Perform a PUT request with no body
+    pub fn put_empty(&self, url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.put_empty(url))?
+    }
+
+    /// Perform a PUT request with a multipart/form-data body
+    pub fn put_multipart(&self, url: impl IntoUrl, form: Form) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.put_multipart(url, form))?
+    }
+
+    /// Perform a PUT request that streams a file from disk as the body
+    pub fn put_file(&self, url: impl IntoUrl, path: impl AsRef<Path>) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.put_file(url, path))?
+    }
+
+    /// Perform a PATCH request with a JSON body
+    pub fn patch_json<T: Serialize + ?Sized>(
+        &self,
+        url: impl IntoUrl,
+        body: &T,
+    ) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.patch_json(url, body))?
+    }
+
+    /// Perform a PATCH request with a raw text or binary body
+    pub fn patch(&self, url: impl IntoUrl, body: impl Into<Body>) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.patch(url, body))?
+    }
+
+    /// Perform a DELETE request
+    pub fn delete(&self, url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.delete(url))?
+    }
+
+    /// Perform a DELETE request with a JSON body
+    pub fn delete_json<T: Serialize + ?Sized>(
+        &self,
+        url: impl IntoUrl,
+        body: &T,
+    ) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.delete_json(url, body))?
+    }
+
+    /// Perform a HEAD request
+    pub fn head(&self, url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+        block_on(self.inner.head(url))?
+    }
+}
+
+/// Iterates over the pages of a paginated API
+///
+/// Mirrors [`http::Paginator`](super::Paginator).
+pub struct Paginator<'a> {
+    inner: super::Paginator<'a>,
+}
+
+impl Paginator<'_> {
+    /// Stop after fetching this many pages
+    pub fn max_pages(self, max_pages: usize) -> Self {
+        Self {
+            inner: self.inner.max_pages(max_pages),
+        }
+    }
+
+    /// Find the next page from a cursor in the JSON body
+    pub fn cursor<S, F>(self, param: S, extract: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&serde_json::Value) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            inner: self.inner.cursor(param, extract),
+        }
+    }
+
+    /// Read items from a JSON pointer instead of the top level of each page
+    pub fn items_pointer<S: Into<String>>(self, pointer: S) -> Self {
+        Self {
+            inner: self.inner.items_pointer(pointer),
+        }
+    }
+
+    /// Fetch the next page, or `None` once there are no more pages
+    pub fn next_page(&mut self) -> XXResult<Option<XXHTTPResponse>> {
+        block_on(self.inner.next_page())?
+    }
+
+    /// Return the next JSON array item, fetching the next page when the current one
+    /// runs out
+    pub fn next_item<T: DeserializeOwned>(&mut self) -> XXResult<Option<T>> {
+        block_on(self.inner.next_item())?
+    }
+
+    /// Fetch all remaining pages and collect the JSON array items from each one
+    pub fn items<T: DeserializeOwned>(self) -> XXResult<Vec<T>> {
+        block_on(self.inner.items())?
+    }
+}
+
+impl Iterator for Paginator<'_> {
+    type Item = XXResult<XXHTTPResponse>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_page().transpose()
+    }
+}
+
+/// Get the contents of a URL
+///
+/// # Example
+/// ```no_run
+/// use xx::http::blocking::get;
+/// let resp = get("https://postman-echo.com/get").unwrap();
+/// println!("{}", resp.text());
+/// ```
+pub fn get(url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+    Client::new().get(url)
+}
+
+/// Get the contents of a URL as bytes
+pub fn get_bytes(url: impl IntoUrl) -> XXResult<Vec<u8>> {
+    Client::new().get_bytes(url)
+}
+
+/// Download a file from a URL
+///
+/// # Example
+/// ```no_run
+/// use xx::http::blocking::download;
+/// download("https://postman-echo.com/get", "/tmp/test.txt").unwrap();
+/// ```
+pub fn download(url: impl IntoUrl, to: impl AsRef<Path>) -> XXResult<()> {
+    Client::new().download(url, to)
+}
+
+/// Perform a POST request with a JSON body
+pub fn post_json<T: Serialize + ?Sized>(url: impl IntoUrl, body: &T) -> XXResult<XXHTTPResponse> {
+    Client::new().post_json(url, body)
+}
+
+/// Perform a POST request with form data
+pub fn post_form<T: Serialize + ?Sized>(url: impl IntoUrl, form: &T) -> XXResult<XXHTTPResponse> {
+    Client::new().post_form(url, form)
+}
+
+/// Perform a PUT request with a JSON body
+pub fn put_json<T: Serialize + ?Sized>(url: impl IntoUrl, body: &T) -> XXResult<XXHTTPResponse> {
+    Client::new().put_json(url, body)
+}
+
+/// Perform a PATCH request with a JSON body
+pub fn patch_json<T: Serialize + ?Sized>(url: impl IntoUrl, body: &T) -> XXResult<XXHTTPResponse> {
+    Client::new().patch_json(url, body)
+}
+
+/// Perform a DELETE request
+pub fn delete(url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+    Client::new().delete(url)
+}
+
+/// Perform a HEAD request
+pub fn head(url: impl IntoUrl) -> XXResult<XXHTTPResponse> {
+    Client::new().head(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path},
+    };
+
+    use super::*;
+
+    /// Start a mock server on its own runtime so the test thread stays synchronous
+    fn mock_server() -> (tokio::runtime::Runtime, MockServer) {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let server = rt.block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/get"))
+                .and(header("Authorization", "Bearer test-token"))
+                .respond_with(ResponseTemplate::new(200).set_body_string("hello"))
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/post"))
+                .respond_with(ResponseTemplate::new(201).set_body_string("created"))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/flaky"))
+                .respond_with(ResponseTemplate::new(503))
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/flaky"))
+                .respond_with(ResponseTemplate::new(200).set_body_string("recovered"))
+                .mount(&server)
+                .await;
+            server
+        });
+        (rt, server)
+    }
+
+    #[test]
+    fn test_blocking_get() {
+        let (_rt, server) = mock_server();
+        let resp = Client::new()
+            .bearer_token("test-token")
+            .get(format!("{}/get", server.uri()))
+            .unwrap();
+        assert_eq!(resp.status, reqwest::StatusCode::OK);
+        assert_eq!(resp.body, "hello");
+    }
+
+    #[test]
+    fn test_blocking_post_json() {
+        let (_rt, server) = mock_server();
+        let resp = post_json(
+            format!("{}/post", server.uri()),
+            &serde_json::json!({"key": "value"}),
+        )
+        .unwrap();
+        assert_eq!(resp.status, reqwest::StatusCode::CREATED);
+        assert_eq!(resp.body, "created");
+    }
+
+    #[test]
+    fn test_blocking_retry() {
+        let (_rt, server) = mock_server();
+        let resp = Client::new()
+            .retry_delay(Duration::from_millis(1))
+            .get(format!("{}/flaky", server.uri()))
+            .unwrap();
+        assert_eq!(resp.body, "recovered");
+    }
+
+    #[test]
+    fn test_blocking_download() {
+        let (_rt, server) = mock_server();
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("out.txt");
+        Client::from(super::super::Client::new().bearer_token("test-token"))
+            .download(format!("{}/get", server.uri()), &file)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_blocking_in_runtime() {
+        let Err(err) = get("http://127.0.0.1:1/") else {
+            panic!("expected an error inside a runtime");
+        };
+        assert!(err.to_string().contains("async runtime"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Request bodies that can be sent again on retry
+//!
+//! Retries rebuild the request from scratch, so a body must be able to produce
+//! itself more than once. In-memory bodies are cloned; file-backed bodies reopen
+//! the file on every attempt so a partially sent stream is never reused.
+
+use std::borrow::Cow;
+use std::future::Future;
+use std::path::PathBuf;
+
+use bytes::Bytes;
+use reqwest::RequestBuilder;
+
+use super::fixtures::Fnv1a;
+use crate::{XXError, XXResult};
+
+/// A request body that can be attached to a fresh request on each attempt
+pub(crate) trait RequestBody: Send + Sync {
+    /// Attach this body (and any headers it implies) to a request
+    fn attach(
+        &self,
+        request: RequestBuilder,
+    ) -> impl Future<Output = XXResult<RequestBuilder>> + Send;
+
+    /// Bytes that identify this body when naming recorded fixtures
+    fn fixture_key(&self) -> XXResult<Cow<'_, [u8]>>;
+}
+
+impl RequestBody for String {
+    async fn attach(&self, request: RequestBuilder) -> XXResult<RequestBuilder> {
+        Ok(request.body(self.clone()))
+    }
+
+    fn fixture_key(&self) -> XXResult<Cow<'_, [u8]>> {
+        Ok(self.as_bytes().into())
+    }
+}
+
+/// A raw request body, either text or binary
+///
+/// Created from strings, byte vectors, byte slices and [`Bytes`]. Cloning it for a
+/// retry doesn't copy the data.
+#[derive(Debug, Clone, Default)]
+pub struct Body(Bytes);
+
+impl Body {
+    /// The body as bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        Self(Bytes::copy_from_slice(bytes))
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Body {
+    fn from(bytes: &[u8; N]) -> Self {
+        Self(Bytes::copy_from_slice(bytes))
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Self(text.into())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Self {
+        Self(Bytes::copy_from_slice(text.as_bytes()))
+    }
+}
+
+impl From<&String> for Body {
+    fn from(text: &String) -> Self {
+        Self(Bytes::copy_from_slice(text.as_bytes()))
+    }
+}
+
+impl RequestBody for Body {
+    async fn attach(&self, request: RequestBuilder) -> XXResult<RequestBuilder> {
+        Ok(request.body(self.0.clone()))
+    }
+
+    fn fixture_key(&self) -> XXResult<Cow<'_, [u8]>> {
+        Ok(self.0.as_ref().into())
+    }
+}
+
+/// A file on disk, streamed as the request body with a known `Content-Length`
+pub(crate) struct FileBody(pub(crate) PathBuf);
+
+impl RequestBody for FileBody {
+    async fn attach(&self, request: RequestBuilder) -> XXResult<RequestBuilder> {
+        let path = &self.0;
+        let file = tokio::fs::File::open(path)
+            .await
+            .map_err(|err| XXError::FileError(err, path.clone()))?;
+        let len = file
+            .metadata()
+            .await
+            .map_err(|err| XXError::FileError(err, path.clone()))?
+            .len();
+        trace!("streaming {} ({} bytes)", path.display(), len);
+        Ok(request
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(reqwest::header::CONTENT_LENGTH, len)
+            .body(file))
+    }
+
+    /// A hash of the file's contents, so different files sent to one URL are recorded
+    /// separately
+    fn fixture_key(&self) -> XXResult<Cow<'_, [u8]>> {
+        let mut hash = Fnv1a::default();
+        hash.write_file(&self.0)?;
+        Ok(hash.0.to_le_bytes().to_vec().into())
+    }
+}
@@ -0,0 +1,274 @@
+//! Per-host credential resolution for the HTTP client
+//!
+//! When no explicit [`Auth`] is set on the [`Client`](super::Client), credentials for
+//! each request are looked up in order from:
+//!
+//! 1. a user-supplied credential provider callback
+//! 2. environment variables mapped to specific hosts (e.g. `GITHUB_TOKEN` for `api.github.com`)
+//! 3. a netrc file (`$NETRC`, or `~/.netrc` / `~/_netrc`)
+//!
+//! Credentials are only attached to the request for the host they were resolved for.
+//! If the server redirects to a different host, scheme or port, the `Authorization`
+//! header is dropped rather than forwarded.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use reqwest::Url;
+
+use super::Auth;
+use crate::file;
+
+/// Callback that returns credentials for a request URL
+pub(crate) type CredentialProvider = Arc<dyn Fn(&Url) -> Option<Auth> + Send + Sync>;
+
+/// Credential sources configured on a [`Client`](super::Client)
+#[derive(Clone, Default)]
+pub(crate) struct CredentialConfig {
+    pub(crate) provider: Option<CredentialProvider>,
+    /// `(env var, host)` pairs for bearer tokens
+    pub(crate) token_env: Vec<(String, String)>,
+    pub(crate) netrc: bool,
+    pub(crate) netrc_file: Option<PathBuf>,
+}
+
+impl CredentialConfig {
+    /// Find credentials for a request URL
+    pub(crate) fn resolve(&self, url: &Url) -> Option<Auth> {
+        let host = url.host_str()?;
+
+        if let Some(provider) = &self.provider
+            && let Some(auth) = provider(url)
+        {
+            trace!("using credentials from provider for {}", host);
+            return Some(auth);
+        }
+
+        for (var, token_host) in &self.token_env {
+            if token_host.eq_ignore_ascii_case(host)
+                && let Some(token) = std::env::var(var).ok().filter(|t| !t.is_empty())
+            {
+                trace!("using credentials from ${} for {}", var, host);
+                return Some(Auth::Bearer(token));
+            }
+        }
+
+        if self.netrc
+            && let Some(path) = self.netrc_path()
+            && let Some(auth) = Netrc::load(&path).and_then(|n| n.find(host))
+        {
+            trace!("using credentials from {} for {}", path.display(), host);
+            return Some(auth);
+        }
+
+        None
+    }
+
+    fn netrc_path(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.netrc_file {
+            return Some(path.clone());
+        }
+        if let Some(path) = std::env::var_os("NETRC").filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        let home = crate::home::home_dir()?;
+        [".netrc", "_netrc"]
+            .into_iter()
+            .map(|name| home.join(name))
+            .find(|path| path.exists())
+    }
+}
+
+/// A parsed netrc file
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Netrc {
+    machines: Vec<(String, NetrcEntry)>,
+    default: Option<NetrcEntry>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct NetrcEntry {
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl Netrc {
+    fn load(path: &Path) -> Option<Self> {
+        if !path.exists() {
+            return None;
+        }
+        match file::read_to_string(path) {
+            Ok(content) => Some(Self::parse(&content)),
+            Err(err) => {
+                warn!("failed to read netrc: {}", err);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn parse(content: &str) -> Self {
+        let mut netrc = Self::default();
+        let mut current: Option<&mut NetrcEntry> = None;
+        let mut lines = content.lines();
+        while let Some(line) = lines.next() {
+            let mut tokens = line.split_whitespace();
+            while let Some(token) = tokens.next() {
+                match token {
+                    "machine" => {
+                        let Some(name) = tokens.next() else { break };
+                        netrc
+                            .machines
+                            .push((name.to_lowercase(), NetrcEntry::default()));
+                        current = netrc.machines.last_mut().map(|(_, e)| e);
+                    }
+                    "default" => {
+                        current = Some(netrc.default.insert(NetrcEntry::default()));
+                    }
+                    "login" => {
+                        if let (Some(entry), Some(value)) = (current.as_deref_mut(), tokens.next())
+                        {
+                            entry.login = Some(value.to_string());
+                        }
+                    }
+                    "password" => {
+                        if let (Some(entry), Some(value)) = (current.as_deref_mut(), tokens.next())
+                        {
+                            entry.password = Some(value.to_string());
+                        }
+                    }
+                    "account" => {
+                        tokens.next();
+                    }
+                    "macdef" => {
+                        // Macro definitions run until the next blank line
+                        for line in lines.by_ref() {
+                            if line.trim().is_empty() {
+                                break;
+                            }
+                        }
+                        break;
+                    }
+                    _ if token.starts_with('#') => break,
+                    _ => {}
+                }
+            }
+        }
+        netrc
+    }
+
+    /// Find credentials for a host, falling back to the `default` entry
+    pub(crate) fn find(&self, host: &str) -> Option<Auth> {
+        let host = host.to_lowercase();
+        let entry = self
+            .machines
+            .iter()
+            .find(|(name, _)| *name == host)
+            .map(|(_, entry)| entry)
+            .or(self.default.as_ref())?;
+        Some(Auth::Basic {
+            username: entry.login.clone()?,
+            password: entry.password.clone().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn basic(auth: Option<Auth>) -> Option<(String, String)> {
+        match auth? {
+            Auth::Basic { username, password } => Some((username, password)),
+            Auth::Bearer(_) => None,
+        }
+    }
+
+    #[test]
+    fn test_netrc_parse() {
+        let netrc = Netrc::parse(
+            r#"
+# comment
+machine artifacts.example.com
+    login alice
+    password s3cret
+machine api.example.com login bob password hunter2 account ops
+
+macdef init
+machine evil.example.com login mallory password nope
+
+default login anonymous password guest@
+"#,
+        );
+        assert_eq!(
+            basic(netrc.find("artifacts.example.com")),
+            Some(("alice".into(), "s3cret".into()))
+        );
+        assert_eq!(
+            basic(netrc.find("API.example.com")),
+            Some(("bob".into(), "hunter2".into()))
+        );
+        assert_eq!(
+            basic(netrc.find("evil.example.com")),
+            Some(("anonymous".into(), "guest@".into()))
+        );
+        assert_eq!(
+            basic(netrc.find("other.example.com")),
+            Some(("anonymous".into(), "guest@".into()))
+        );
+    }
+
+    #[test]
+    fn test_netrc_no_default() {
+        let netrc = Netrc::parse("machine a.example.com login a password b");
+        assert!(netrc.find("b.example.com").is_none());
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let netrc = tmp.path().join("netrc");
+        std::fs::write(
+            &netrc,
+            "machine a.example.com login netrc password pass\nmachine b.example.com login netrc password pass\n",
+        )
+        .unwrap();
+
+        let config = CredentialConfig {
+            provider: Some(Arc::new(|url: &Url| {
+                (url.host_str() == Some("a.example.com")).then(|| Auth::Bearer("provided".into()))
+            })),
+            netrc: true,
+            netrc_file: Some(netrc),
+            ..Default::default()
+        };
+
+        let url = |s: &str| Url::parse(s).unwrap();
+        assert!(matches!(
+            config.resolve(&url("https://a.example.com/x")),
+            Some(Auth::Bearer(t)) if t == "provided"
+        ));
+        assert_eq!(
+            basic(config.resolve(&url("https://b.example.com/x"))),
+            Some(("netrc".into(), "pass".into()))
+        );
+        assert!(config.resolve(&url("https://c.example.com/x")).is_none());
+    }
+
+    #[test]
+    fn test_netrc_disabled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let netrc = tmp.path().join("netrc");
+        std::fs::write(&netrc, "default login a password b").unwrap();
+        let config = CredentialConfig {
+            netrc_file: Some(netrc),
+            ..Default::default()
+        };
+        assert!(
+            config
+                .resolve(&Url::parse("https://example.com").unwrap())
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Concurrent downloads of many files
+//!
+//! [`DownloadManager`] runs a batch of downloads on a shared [`Client`] with a
+//! concurrency limit. Every job goes through [`Client::download`], so retries,
+//! authentication and proxy settings apply to each one. A failing job doesn't stop
+//! the others; each job gets its own [`DownloadResult`].
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::http::{Client, DownloadJob, DownloadManager};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let results = DownloadManager::new(Client::new().retries(2))
+//!         .concurrency(4)
+//!         .on_progress(|p| eprintln!("{} bytes of {:?}", p.bytes_downloaded, p.total_bytes))
+//!         .run([
+//!             DownloadJob::new("https://example.com/a.tar.gz", "/tmp/a.tar.gz"),
+//!             DownloadJob::new("https://example.com/b.tar.gz", "/tmp/b.tar.gz"),
+//!         ])
+//!         .await;
+//!     for result in results {
+//!         if let Err(err) = &result.result {
+//!             eprintln!("{} failed: {}", result.url, err);
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::Client;
+use crate::{XXError, XXResult, error};
+
+/// Default number of downloads run at the same time
+pub const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 4;
+
+/// Minimum time between progress updates for a single transfer
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress of a single transfer
+///
+/// Passed to [`Client::get_bytes_with_progress`] and [`Client::download_with_progress`]
+/// callbacks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Bytes of the body received so far
+    pub bytes: u64,
+    /// Total size of the body from `Content-Length`, if the server sent it
+    pub total: Option<u64>,
+}
+
+/// A single file to download
+#[derive(Debug, Clone)]
+pub struct DownloadJob {
+    url: String,
+    to: PathBuf,
+    #[cfg(feature = "hash")]
+    checksum: Option<String>,
+}
+
+impl DownloadJob {
+    /// Create a job that downloads `url` to the path `to`
+    pub fn new<U: Into<String>, P: AsRef<Path>>(url: U, to: P) -> Self {
+        Self {
+            url: url.into(),
+            to: to.as_ref().to_path_buf(),
+            #[cfg(feature = "hash")]
+            checksum: None,
+        }
+    }
+
+    /// Verify the downloaded file against a checksum (requires `hash` feature)
+    ///
+    /// Accepts `sha256:<hex>`, `sha512:<hex>` or a bare SHA256 hex digest.
+    /// On mismatch the job fails and an existing file at the destination is left
+    /// untouched.
+    #[cfg(feature = "hash")]
+    pub fn checksum<S: Into<String>>(mut self, checksum: S) -> Self {
+        self.checksum = Some(checksum.into());
+        self
+    }
+}
+
+impl<U: Into<String>, P: AsRef<Path>> From<(U, P)> for DownloadJob {
+    fn from((url, to): (U, P)) -> Self {
+        Self::new(url, to)
+    }
+}
+
+/// The outcome of a single [`DownloadJob`]
+#[derive(Debug)]
+pub struct DownloadResult {
+    /// The URL that was downloaded
+    pub url: String,
+    /// Where the file was written
+    pub path: PathBuf,
+    /// The size of the downloaded file in bytes, or why the download failed
+    pub result: XXResult<u64>,
+}
+
+/// Aggregated progress across all jobs in a [`DownloadManager::run`] call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Number of jobs in the batch
+    pub total_jobs: usize,
+    /// Number of jobs that finished successfully
+    pub completed_jobs: usize,
+    /// Number of jobs that failed
+    pub failed_jobs: usize,
+    /// Bytes received so far by running and finished jobs, not counting failed ones
+    pub bytes_downloaded: u64,
+    /// Size of every job that hasn't failed, once they have all started and their
+    /// servers sent `Content-Length`
+    pub total_bytes: Option<u64>,
+}
+
+type ProgressCallback = Arc<dyn Fn(&DownloadProgress) + Send + Sync>;
+
+/// Progress of every job in a batch, aggregated into a [`DownloadProgress`]
+struct ProgressTracker {
+    progress: DownloadProgress,
+    /// The latest progress of each job, or `None` before it starts
+    jobs: Vec<Option<TransferProgress>>,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl ProgressTracker {
+    fn new(total_jobs: usize, on_progress: Option<ProgressCallback>) -> Self {
+        Self {
+            progress: DownloadProgress {
+                total_jobs,
+                ..Default::default()
+            },
+            jobs: vec![None; total_jobs],
+            on_progress,
+        }
+    }
+
+    fn update(&mut self, job: usize, transfer: TransferProgress) {
+        self.jobs[job] = Some(transfer);
+        self.report();
+    }
+
+    fn finish(&mut self, job: usize, ok: bool) {
+        match ok {
+            true => self.progress.completed_jobs += 1,
+            false => {
+                self.progress.failed_jobs += 1;
+                // Failed jobs no longer count towards the bytes of the batch
+                self.jobs[job] = Some(TransferProgress {
+                    bytes: 0,
+                    total: Some(0),
+                });
+            }
+        }
+        self.report();
+    }
+
+    fn report(&mut self) {
+        self.progress.bytes_downloaded = self.jobs.iter().flatten().map(|j| j.bytes).sum();
+        self.progress.total_bytes = self
+            .jobs
+            .iter()
+            .map(|j| j.and_then(|j| j.total))
+            .sum::<Option<u64>>();
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&self.progress);
+        }
+    }
+}
+
+/// Suffix counter for temporary download files, unique within this process
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs many downloads concurrently on a shared [`Client`]
+pub struct DownloadManager {
+    client: Arc<Client>,
+    concurrency: usize,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl Default for DownloadManager {
+    fn default() -> Self {
+        Self::new(Client::new())
+    }
+}
+
+impl DownloadManager {
+    /// Create a download manager that uses `client` for every job
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(client),
+            concurrency: DEFAULT_DOWNLOAD_CONCURRENCY,
+            on_progress: None,
+        }
+    }
+
+    /// Set the maximum number of downloads running at the same time
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Call `f` with aggregated progress as bytes arrive and whenever a job finishes
+    ///
+    /// Updates for each job are throttled like [`Client::download_with_progress`].
+    pub fn on_progress<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&DownloadProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
+
+    /// Download all jobs, returning one result per job in the same order
+    pub async fn run<I, J>(&self, jobs: I) -> Vec<DownloadResult>
+    where
+        I: IntoIterator<Item = J>,
+        J: Into<DownloadJob>,
+    {
+        let jobs: Vec<DownloadJob> = jobs.into_iter().map(Into::into).collect();
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let tracker = Arc::new(Mutex::new(ProgressTracker::new(
+            jobs.len(),
+            self.on_progress.clone(),
+        )));
+        let mut results: Vec<DownloadResult> = jobs
+            .iter()
+            .map(|job| DownloadResult {
+                url: job.url.clone(),
+                path: job.to.clone(),
+                result: Err(error!("download of {} did not complete", job.url)),
+            })
+            .collect();
+        let mut tasks = HashMap::new();
+        let mut set = JoinSet::new();
+
+        for (i, job) in jobs.into_iter().enumerate() {
+            let client = self.client.clone();
+            let semaphore = semaphore.clone();
+            let tracker = tracker.clone();
+            let handle = set.spawn(async move {
+                match semaphore.acquire_owned().await {
+                    Ok(_permit) => {
+                        download_job(&client, &job, |p| {
+                            tracker
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .update(i, *p)
+                        })
+                        .await
+                    }
+                    Err(err) => Err(error!("download manager closed: {}", err)),
+                }
+            });
+            tasks.insert(handle.id(), i);
+        }
+
+        while let Some(joined) = set.join_next_with_id().await {
+            let (i, result) = match joined {
+                Ok((id, result)) => (tasks[&id], result),
+                // A panicking or cancelled task only fails its own job
+                Err(err) => {
+                    let i = tasks[&err.id()];
+                    warn!("download task for {} failed: {}", results[i].url, err);
+                    let err = error!("download of {} failed: {}", results[i].url, err);
+                    (i, Err(err))
+                }
+            };
+            // A callback that panicked in a job's task poisons the lock, but the
+            // tracker is left consistent
+            tracker
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .finish(i, result.is_ok());
+            results[i].result = result;
+        }
+
+        results
+    }
+}
+
+/// Download a job to a temporary file next to its destination, moving it into place
+/// once it is complete and matches its checksum
+async fn download_job<F>(client: &Client, job: &DownloadJob, on_progress: F) -> XXResult<u64>
+where
+    F: Fn(&TransferProgress) + Send + Sync,
+{
+    trace!("downloading {} to {}", job.url, job.to.display());
+    let tmp = TempFile(temp_path(&job.to));
+    client
+        .download_with_progress(job.url.as_str(), &tmp.0, on_progress)
+        .await?;
+    #[cfg(feature = "hash")]
+    if let Some(checksum) = &job.checksum {
+        verify_checksum(&tmp.0, checksum)?;
+    }
+    let size = std::fs::metadata(&tmp.0)
+        .map_err(|err| XXError::FileError(err, tmp.0.clone()))?
+        .len();
+    crate::file::mv(&tmp.0, &job.to)?;
+    Ok(size)
+}
+
+/// Removes a partial download when dropped, including when its task panics or is
+/// cancelled; does nothing once the file has been moved into place
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            let _ = crate::file::remove_file(&self.0);
+        }
+    }
+}
+
+/// A hidden, unique path in the same directory as `to`, so moving it there is atomic
+fn temp_path(to: &Path) -> PathBuf {
+    let name = to
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let n = TEMP_FILES.fetch_add(1, Ordering::Relaxed);
+    to.with_file_name(format!(".{name}.{}.{n}.part", std::process::id()))
+}
+
+/// Read a response body chunk by chunk, passing each one to `write` and reporting
+/// throttled progress
+pub(crate) async fn read_body<W>(
+    mut resp: reqwest::Response,
+    url: &str,
+    on_progress: Option<&(dyn Fn(&TransferProgress) + Send + Sync)>,
+    mut write: W,
+) -> XXResult<()>
+where
+    W: FnMut(&[u8]) -> XXResult<()>,
+{
+    let mut progress = TransferProgress {
+        bytes: 0,
+        total: resp.content_length(),
+    };
+    let mut last_report = None::<Instant>;
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|err| XXError::HTTPError(err, url.to_string()))?
+    {
+        write(&chunk)?;
+        progress.bytes += chunk.len() as u64;
+        if let Some(on_progress) = on_progress
+            && last_report.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL)
+        {
+            on_progress(&progress);
+            last_report = Some(Instant::now());
+        }
+    }
+    if let Some(on_progress) = on_progress {
+        on_progress(&progress);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "hash")]
+fn verify_checksum(path: &Path, checksum: &str) -> XXResult<()> {
+    match checksum.split_once(':') {
+        Some(("sha256", hex)) => crate::hash::ensure_checksum_sha256(path, hex),
+        Some(("sha512", hex)) => crate::hash::ensure_checksum_sha512(path, hex),
+        Some((algo, _)) => Err(error!("unsupported checksum algorithm: {}", algo)),
+        None => crate::hash::ensure_checksum_sha256(path, checksum),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    use super::*;
+
+    async fn setup_mock_server() -> MockServer {
+        let mock_server = MockServer::start().await;
+        for (name, body) in [("a", "alpha"), ("b", "bravo"), ("c", "charlie")] {
+            Mock::given(method("GET"))
+                .and(path(format!("/{name}.txt")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/missing.txt"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        mock_server
+    }
+
+    #[test(tokio::test)]
+    async fn test_download_manager() {
+        let mock_server = setup_mock_server().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let updates = Arc::new(Mutex::new(vec![]));
+
+        let results = {
+            let updates = updates.clone();
+            DownloadManager::new(Client::new().retries(0))
+                .concurrency(2)
+                .on_progress(move |p| updates.lock().unwrap().push(p.clone()))
+                .run(["a", "missing", "b", "c"].map(|name| {
+                    (
+                        format!("{}/{name}.txt", mock_server.uri()),
+                        tmp.path().join(format!("{name}.txt")),
+                    )
+                }))
+                .await
+        };
+
+        assert_eq!(results.len(), 4);
+        assert!(results[0].url.ends_with("/a.txt"));
+        assert_eq!(results[0].result.as_ref().unwrap(), &5);
+        assert!(results[1].result.is_err());
+        assert_eq!(results[2].result.as_ref().unwrap(), &5);
+        assert_eq!(results[3].result.as_ref().unwrap(), &7);
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("c.txt")).unwrap(),
+            "charlie"
+        );
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(
+            updates.last().unwrap(),
+            &DownloadProgress {
+                total_jobs: 4,
+                completed_jobs: 3,
+                failed_jobs: 1,
+                bytes_downloaded: 17,
+                total_bytes: Some(17),
+            }
+        );
+        // No temporary files are left behind
+        let mut files: Vec<_> = std::fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["a.txt", "b.txt", "c.txt"]);
+    }
+
+    #[test(tokio::test)]
+    async fn test_download_manager_byte_progress() {
+        let mock_server = MockServer::start().await;
+        let body = vec![7u8; 256 * 1024];
+        Mock::given(method("GET"))
+            .and(path("/big.bin"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&mock_server)
+            .await;
+        let tmp = tempfile::tempdir().unwrap();
+        let updates = Arc::new(Mutex::new(vec![]));
+
+        let results = {
+            let updates = updates.clone();
+            DownloadManager::default()
+                .on_progress(move |p| updates.lock().unwrap().push(p.clone()))
+                .run([(
+                    format!("{}/big.bin", mock_server.uri()),
+                    tmp.path().join("big.bin"),
+                )])
+                .await
+        };
+        assert_eq!(results[0].result.as_ref().unwrap(), &(body.len() as u64));
+
+        // Bytes are reported before the job finishes
+        let updates = updates.lock().unwrap();
+        let running = updates.iter().filter(|p| p.completed_jobs == 0).count();
+        assert!(running >= 1, "{updates:?}");
+        assert!(
+            updates
+                .windows(2)
+                .all(|w| w[0].bytes_downloaded <= w[1].bytes_downloaded)
+        );
+        assert_eq!(updates[0].total_bytes, Some(body.len() as u64));
+    }
+
+    #[test(tokio::test)]
+    async fn test_download_manager_task_panic() {
+        let mock_server = setup_mock_server().await;
+        let tmp = tempfile::tempdir().unwrap();
+
+        // The callback panics inside the job's task while it is running
+        let results = DownloadManager::default()
+            .on_progress(|p| {
+                if p.completed_jobs + p.failed_jobs == 0 && p.bytes_downloaded > 0 {
+                    panic!("progress callback panicked");
+                }
+            })
+            .run([(
+                format!("{}/a.txt", mock_server.uri()),
+                tmp.path().join("a.txt"),
+            )])
+            .await;
+        assert_eq!(results.len(), 1);
+        let err = results[0].result.as_ref().unwrap_err();
+        assert!(err.to_string().contains("panicked"), "{err}");
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[test(tokio::test)]
+    async fn test_download_with_progress() {
+        let mock_server = MockServer::start().await;
+        let body = vec![7u8; 256 * 1024];
+        Mock::given(method("GET"))
+            .and(path("/big.bin"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&mock_server)
+            .await;
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("big.bin");
+        let updates = Mutex::new(vec![]);
+
+        Client::new()
+            .download_with_progress(format!("{}/big.bin", mock_server.uri()), &file, |p| {
+                // The body is written to disk as it arrives rather than at the end
+                assert_eq!(std::fs::metadata(&file).unwrap().len(), p.bytes);
+                updates.lock().unwrap().push(*p)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&file).unwrap(), body);
+        let updates = updates.into_inner().unwrap();
+        assert!(updates.len() >= 2);
+        assert!(updates.windows(2).all(|w| w[0].bytes <= w[1].bytes));
+        assert_eq!(
+            updates.last().unwrap(),
+            &TransferProgress {
+                bytes: body.len() as u64,
+                total: Some(body.len() as u64),
+            }
+        );
+    }
+
+    #[cfg(feature = "hash")]
+    #[test(tokio::test)]
+    async fn test_download_manager_checksum() {
+        let mock_server = setup_mock_server().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let good = tmp.path().join("a.txt");
+        let bad = tmp.path().join("b.txt");
+
+        let results = DownloadManager::default()
+            .run([
+                DownloadJob::new(format!("{}/a.txt", mock_server.uri()), &good)
+                    .checksum(format!("sha256:{}", crate::hash::sha256(b"alpha"))),
+                DownloadJob::new(format!("{}/b.txt", mock_server.uri()), &bad)
+                    .checksum(crate::hash::sha256(b"not bravo")),
+            ])
+            .await;
+
+        assert!(results[0].result.is_ok());
+        assert!(good.exists());
+        let err = results[1].result.as_ref().unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+        assert!(!bad.exists());
+
+        // A failed re-download leaves the existing file alone
+        std::fs::write(&bad, "previous").unwrap();
+        let results = DownloadManager::default()
+            .run([
+                DownloadJob::new(format!("{}/b.txt", mock_server.uri()), &bad)
+                    .checksum(crate::hash::sha256(b"not bravo")),
+            ])
+            .await;
+        assert!(results[0].result.is_err());
+        assert_eq!(std::fs::read_to_string(&bad).unwrap(), "previous");
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 2);
+    }
+}
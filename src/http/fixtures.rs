@@ -0,0 +1,374 @@
+//! Recording and replaying HTTP fixtures
+//!
+//! With [`Client::fixtures`](super::Client::fixtures) set, responses are read from JSON
+//! files in a fixtures directory instead of the network. Set `XX_HTTP_FIXTURES=record`
+//! (or use [`Client::fixture_mode`](super::Client::fixture_mode)) to make real requests
+//! and write their responses there, then commit the files and run tests without a
+//! network.
+//!
+//! Each fixture is named after the request method, host, path and a hash of the full
+//! URL and body, including the contents of uploaded files. Credentials are never
+//! written: sensitive headers such as `Authorization` and `Cookie`, and query
+//! parameters that look like tokens, are replaced with `[REDACTED]`.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Url};
+
+use super::redact::{REDACTED, is_sensitive, redact_url};
+use super::redirect::RedirectChain;
+use super::response::StoredBody;
+use crate::{XXError, XXResult, error, file};
+
+/// Environment variable that selects the fixture mode (`record` or `replay`)
+pub const FIXTURES_MODE_ENV: &str = "XX_HTTP_FIXTURES";
+
+/// Whether fixtures are read or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Serve responses from fixtures, failing if one is missing
+    Replay,
+    /// Make real requests and save their responses as fixtures
+    Record,
+}
+
+impl FixtureMode {
+    /// Read the mode from `XX_HTTP_FIXTURES`, defaulting to [`FixtureMode::Replay`]
+    pub fn from_env() -> Self {
+        match std::env::var(FIXTURES_MODE_ENV).as_deref() {
+            Ok("record") => Self::Record,
+            Ok("replay") | Ok("") | Err(_) => Self::Replay,
+            Ok(other) => {
+                warn!("unknown {} value {}, replaying", FIXTURES_MODE_ENV, other);
+                Self::Replay
+            }
+        }
+    }
+}
+
+/// Fixture settings for a request
+pub(crate) struct FixtureConfig {
+    pub(crate) dir: PathBuf,
+    pub(crate) mode: FixtureMode,
+}
+
+impl FixtureConfig {
+    /// Build a response from the fixture for a request
+    pub(crate) fn replay(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: &[u8],
+    ) -> XXResult<reqwest::Response> {
+        let path = self.path(method, url, body);
+        if !path.exists() {
+            return Err(error!(
+                "no HTTP fixture for {} {} (expected {}); run with {}=record to create it",
+                method,
+                redact_url(url),
+                path.display(),
+                FIXTURES_MODE_ENV
+            ));
+        }
+        trace!("replaying {} {} from {}", method, url, path.display());
+        let fixture: Fixture = serde_json::from_str(&file::read_to_string(&path)?)
+            .map_err(|err| error!("invalid HTTP fixture {}: {}", path.display(), err))?;
+        fixture.into_response(url)
+    }
+
+    /// Save a response as a fixture, returning an equivalent response to process
+    pub(crate) async fn record(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: &[u8],
+        request_headers: &HeaderMap,
+        resp: reqwest::Response,
+    ) -> XXResult<reqwest::Response> {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let final_url = resp.url().clone();
+        let chain = resp
+            .extensions()
+            .get::<RedirectChain>()
+            .cloned()
+            .unwrap_or_default();
+        let bytes = resp
+            .bytes()
+            .await
+            .map_err(|err| XXError::HTTPError(err, url.to_string()))?;
+        let fixture = Fixture {
+            method: method.to_string(),
+            url: redact_url(url),
+            request_headers: sanitize_headers(request_headers),
+            final_url: (final_url != *url).then(|| redact_url(&final_url)),
+            redirects: chain.0.iter().map(redact_url).collect(),
+            status: status.as_u16(),
+            headers: sanitize_headers(&headers),
+            body: (&bytes).into(),
+        };
+        let path = self.path(method, url, body);
+        trace!("recording {} {} to {}", method, url, path.display());
+        let json = serde_json::to_string_pretty(&fixture)
+            .map_err(|err| error!("failed to serialize HTTP fixture: {}", err))?;
+        file::write(&path, json)?;
+
+        let mut builder = ::http::Response::builder().status(status);
+        for (name, value) in &headers {
+            builder = builder.header(name, value);
+        }
+        build_response(builder, final_url, chain, bytes)
+    }
+
+    fn path(&self, method: &Method, url: &Url, body: &[u8]) -> PathBuf {
+        let mut hash = Fnv1a::default();
+        hash.write(method.as_str().as_bytes());
+        hash.write(url.as_str().as_bytes());
+        hash.write(body);
+        let slug: String = format!("{}{}", url.host_str().unwrap_or(""), url.path())
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .take(80)
+            .collect();
+        self.dir.join(format!(
+            "{}-{}-{:016x}.json",
+            method.as_str().to_lowercase(),
+            slug.trim_matches('_'),
+            hash.0
+        ))
+    }
+}
+
+/// A recorded request and response
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Fixture {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    final_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redirects: Vec<String>,
+    request_headers: Vec<(String, String)>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: StoredBody,
+}
+
+impl Fixture {
+    fn into_response(self, url: &Url) -> XXResult<reqwest::Response> {
+        let mut builder = ::http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let final_url = self
+            .final_url
+            .and_then(|u| Url::parse(&u).ok())
+            .unwrap_or_else(|| url.clone());
+        let chain = RedirectChain(
+            self.redirects
+                .iter()
+                .filter_map(|u| Url::parse(u).ok())
+                .collect(),
+        );
+        build_response(builder, final_url, chain, self.body.into())
+    }
+}
+
+fn build_response(
+    builder: ::http::response::Builder,
+    url: Url,
+    chain: RedirectChain,
+    body: Bytes,
+) -> XXResult<reqwest::Response> {
+    use reqwest::ResponseBuilderExt;
+
+    let resp = builder
+        .url(url)
+        .extension(chain)
+        .body(body)
+        .map_err(|err| error!("invalid HTTP fixture response: {}", err))?;
+    Ok(resp.into())
+}
+
+fn sanitize_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match is_sensitive(name.as_str()) {
+                true => REDACTED.to_string(),
+                false => value.to_str().unwrap_or(REDACTED).to_string(),
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// 64-bit FNV-1a, used for fixture names because it's stable across Rust versions
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+        self.end_field();
+    }
+
+    /// Hash a file's contents as one field, without reading it into memory
+    pub(crate) fn write_file(&mut self, path: &Path) -> XXResult<()> {
+        let mut file = file::open(path)?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => self.update(&buf[..n]),
+                Err(err) => return Err(XXError::FileError(err, path.to_path_buf())),
+            }
+        }
+        self.end_field();
+        Ok(())
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Separate fields so ("ab", "c") and ("a", "bc") hash differently
+    fn end_field(&mut self) {
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x100000001b3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_string_contains, method, path},
+    };
+
+    use super::super::Client;
+    use super::super::multipart::Form;
+    use super::*;
+
+    #[test(tokio::test)]
+    async fn test_record_and_replay() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/releases"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Set-Cookie", "session=abc")
+                    .insert_header("X-Request-Id", "42")
+                    .set_body_string("[1, 2, 3]"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0xff, 0x00]))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let tmp = tempfile::tempdir().unwrap();
+        let url = format!("{}/releases?access_token=hunter2&page=1", mock_server.uri());
+        let rpc = format!("{}/rpc", mock_server.uri());
+
+        let recorder = Client::new()
+            .bearer_token("s3cret")
+            .fixtures(tmp.path())
+            .fixture_mode(FixtureMode::Record);
+        let resp = recorder.get(&url).await.unwrap();
+        assert_eq!(resp.text(), "[1, 2, 3]");
+        recorder.post(&rpc, b"\x01").await.unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 2);
+        let get_fixture = files
+            .iter()
+            .find(|p| p.file_name().unwrap().to_str().unwrap().starts_with("get-"))
+            .unwrap();
+        let json = std::fs::read_to_string(get_fixture).unwrap();
+        assert!(!json.contains("hunter2"));
+        assert!(!json.contains("s3cret"));
+        assert!(!json.contains("session=abc"));
+        assert!(json.contains("X-Request-Id") || json.contains("x-request-id"));
+
+        // Replay doesn't touch the server, which only expects one request of each kind
+        let replayer = Client::new()
+            .fixtures(tmp.path())
+            .fixture_mode(FixtureMode::Replay)
+            .offline(true);
+        let resp = replayer.get(&url).await.unwrap();
+        assert_eq!(resp.status, reqwest::StatusCode::OK);
+        assert_eq!(resp.text(), "[1, 2, 3]");
+        assert_eq!(resp.headers["x-request-id"], "42");
+        let resp = replayer.post(&rpc, b"\x01").await.unwrap();
+        assert_eq!(resp.bytes(), &[0xff, 0x00]);
+
+        // A different body is a different fixture
+        let err = replayer.post(&rpc, b"\x02").await.unwrap_err();
+        assert!(err.to_string().contains("no HTTP fixture"));
+    }
+
+    #[test(tokio::test)]
+    async fn test_record_and_replay_uploads() {
+        let mock_server = MockServer::start().await;
+        for (content, reply) in [("alpha", "first"), ("bravo", "second")] {
+            Mock::given(method("POST"))
+                .and(path("/upload"))
+                .and(body_string_contains(content))
+                .respond_with(ResponseTemplate::new(200).set_body_string(reply))
+                .expect(2)
+                .mount(&mock_server)
+                .await;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let fixtures = tmp.path().join("fixtures");
+        let (a, b) = (tmp.path().join("a.txt"), tmp.path().join("b.txt"));
+        std::fs::write(&a, "alpha").unwrap();
+        std::fs::write(&b, "bravo").unwrap();
+        let url = format!("{}/upload", mock_server.uri());
+
+        let recorder = Client::new()
+            .fixtures(&fixtures)
+            .fixture_mode(FixtureMode::Record);
+        let replayer = Client::new()
+            .fixtures(&fixtures)
+            .fixture_mode(FixtureMode::Replay)
+            .offline(true);
+        for client in [&recorder, &replayer] {
+            let resp = client.post_file(&url, &a).await.unwrap();
+            assert_eq!(resp.text(), "first");
+            let resp = client.post_file(&url, &b).await.unwrap();
+            assert_eq!(resp.text(), "second");
+            let form = Form::new().text("name", "x").file("asset", &a);
+            assert_eq!(
+                client.post_multipart(&url, form).await.unwrap().text(),
+                "first"
+            );
+            let form = Form::new().text("name", "x").file("asset", &b);
+            assert_eq!(
+                client.post_multipart(&url, form).await.unwrap().text(),
+                "second"
+            );
+        }
+        assert_eq!(std::fs::read_dir(&fixtures).unwrap().count(), 4);
+    }
+}
@@ -0,0 +1,324 @@
+//! Request and response middleware for the HTTP client
+//!
+//! A [`Middleware`] sees every request the [`Client`](super::Client) sends over the
+//! network, including retry attempts and redirect hops, and every response or error
+//! that comes back. Use it for cross-cutting behavior such as request signing,
+//! metrics or custom logging.
+//!
+//! Middleware runs in the order it was added for requests and in reverse order for
+//! responses, so the first middleware added wraps all the others. Responses served
+//! from the cache or from fixtures don't go through middleware.
+//!
+//! Two middlewares are built in: [`Timing`] logs how long each request took, and
+//! [`DebugLog`] logs request and response headers with credentials redacted.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::XXResult;
+//! use xx::http::Client;
+//! use xx::http::middleware::{DebugLog, Middleware, Timing};
+//!
+//! struct Signer;
+//!
+//! impl Middleware for Signer {
+//!     fn on_request(&self, request: &mut reqwest::Request) -> XXResult<()> {
+//!         let signature = format!("{:x}", request.url().as_str().len());
+//!         request
+//!             .headers_mut()
+//!             .insert("X-Signature", signature.parse().unwrap());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = Client::new()
+//!         .middleware(Timing)
+//!         .middleware(DebugLog)
+//!         .middleware(Signer);
+//!     let resp = client.get("https://api.example.com/items").await.unwrap();
+//! }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Url};
+
+use super::redact::{REDACTED, is_sensitive, redact_url};
+use crate::{XXError, XXResult};
+
+/// Hooks called around each request sent by a [`Client`](super::Client)
+///
+/// All methods have default implementations that do nothing, so implementors only
+/// override the hooks they need. Returning an error from [`on_request`] or
+/// [`on_response`] fails the request without retrying.
+///
+/// [`on_request`]: Middleware::on_request
+/// [`on_response`]: Middleware::on_response
+pub trait Middleware: Send + Sync {
+    /// Inspect or modify a request before it is sent
+    fn on_request(&self, request: &mut reqwest::Request) -> XXResult<()> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Inspect or modify a response before the client processes it
+    fn on_response(&self, request: &RequestInfo, response: &mut reqwest::Response) -> XXResult<()> {
+        let _ = (request, response);
+        Ok(())
+    }
+
+    /// Observe a request that failed without a response, such as a timeout
+    fn on_error(&self, request: &RequestInfo, error: &XXError) {
+        let _ = (request, error);
+    }
+}
+
+/// The request a response or error belongs to
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    /// HTTP method
+    pub method: Method,
+    /// Request URL
+    pub url: Url,
+    /// Headers that were sent, after all middleware ran
+    pub headers: HeaderMap,
+    /// Retry attempt, starting at 0 for the first try
+    pub attempt: u32,
+    /// Time from sending the request until the response headers arrived or it failed
+    pub elapsed: Duration,
+}
+
+/// Logs the method, URL, status and duration of each request at debug level
+///
+/// Credentials in the URL are redacted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing;
+
+impl Middleware for Timing {
+    fn on_response(&self, request: &RequestInfo, response: &mut reqwest::Response) -> XXResult<()> {
+        debug!(
+            "{} {} -> {} in {:?}{}",
+            request.method,
+            redact_url(&request.url),
+            response.status(),
+            request.elapsed,
+            retry_suffix(request.attempt)
+        );
+        Ok(())
+    }
+
+    fn on_error(&self, request: &RequestInfo, error: &XXError) {
+        debug!(
+            "{} {} failed after {:?}{}: {}",
+            request.method,
+            redact_url(&request.url),
+            request.elapsed,
+            retry_suffix(request.attempt),
+            error
+        );
+    }
+}
+
+fn retry_suffix(attempt: u32) -> String {
+    match attempt {
+        0 => String::new(),
+        n => format!(" (retry {n})"),
+    }
+}
+
+/// Logs request and response headers at debug level
+///
+/// Values of headers that carry credentials, such as `Authorization`, `Cookie` and
+/// anything that looks like a token, are replaced with `[REDACTED]`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugLog;
+
+impl Middleware for DebugLog {
+    fn on_request(&self, request: &mut reqwest::Request) -> XXResult<()> {
+        debug!(
+            "> {} {} {:?}",
+            request.method(),
+            redact_url(request.url()),
+            RedactedHeaders(request.headers())
+        );
+        Ok(())
+    }
+
+    fn on_response(&self, request: &RequestInfo, response: &mut reqwest::Response) -> XXResult<()> {
+        debug!(
+            "< {} {} {:?}",
+            response.status(),
+            redact_url(&request.url),
+            RedactedHeaders(response.headers())
+        );
+        Ok(())
+    }
+}
+
+/// Formats headers for debug output with credentials redacted
+///
+/// ```rust
+/// use reqwest::header::{AUTHORIZATION, HeaderMap};
+/// use xx::http::middleware::RedactedHeaders;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert(AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+/// assert_eq!(
+///     format!("{:?}", RedactedHeaders(&headers)),
+///     r#"{"authorization": "[REDACTED]"}"#
+/// );
+/// ```
+pub struct RedactedHeaders<'a>(pub &'a HeaderMap);
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, value)| {
+                let value = match is_sensitive(name.as_str()) {
+                    true => REDACTED,
+                    false => value.to_str().unwrap_or(REDACTED),
+                };
+                (name.as_str(), value)
+            }))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path},
+    };
+
+    use super::super::Client;
+    use super::*;
+
+    /// Records every hook call so tests can check what middleware saw
+    #[derive(Clone, Default)]
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn on_request(&self, request: &mut reqwest::Request) -> XXResult<()> {
+            self.calls.lock().unwrap().push(format!(
+                "{} request {}",
+                self.name,
+                request.url().path()
+            ));
+            request
+                .headers_mut()
+                .insert("X-Signed-By", self.name.parse().unwrap());
+            Ok(())
+        }
+
+        fn on_response(
+            &self,
+            request: &RequestInfo,
+            response: &mut reqwest::Response,
+        ) -> XXResult<()> {
+            self.calls.lock().unwrap().push(format!(
+                "{} response {} attempt {}",
+                self.name,
+                response.status().as_u16(),
+                request.attempt
+            ));
+            Ok(())
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn test_middleware_order_and_retries() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .and(header("X-Signed-By", "inner"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&mock_server)
+            .await;
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let client = Client::new()
+            .retry_delay(Duration::from_millis(1))
+            .middleware(Timing)
+            .middleware(DebugLog)
+            .middleware(Recorder {
+                name: "outer",
+                calls: calls.clone(),
+            })
+            .middleware(Recorder {
+                name: "inner",
+                calls: calls.clone(),
+            });
+        let resp = client
+            .get(format!("{}/flaky", mock_server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(resp.text(), "ok");
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "outer request /flaky",
+                "inner request /flaky",
+                "inner response 503 attempt 0",
+                "outer response 503 attempt 0",
+                "outer request /flaky",
+                "inner request /flaky",
+                "inner response 200 attempt 1",
+                "outer response 200 attempt 1",
+            ]
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn test_middleware_error() {
+        struct Deny;
+
+        impl Middleware for Deny {
+            fn on_request(&self, request: &mut reqwest::Request) -> XXResult<()> {
+                Err(crate::error!("denied {}", request.url().path()))
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let err = Client::new()
+            .middleware(Deny)
+            .get(format!("{}/secret", mock_server.uri()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("denied /secret"));
+    }
+
+    #[test]
+    fn test_redacted_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Basic dXNlcjpwYXNz".parse().unwrap());
+        headers.insert("x-api-key", "abc".parse().unwrap());
+        headers.insert("accept", "application/json".parse().unwrap());
+        let output = format!("{:?}", RedactedHeaders(&headers));
+        assert!(!output.contains("dXNlcjpwYXNz"));
+        assert!(!output.contains("abc"));
+        assert!(output.contains(r#""accept": "application/json""#));
+    }
+}
@@ -0,0 +1,182 @@
+//! Multipart/form-data request bodies
+//!
+//! A [`Form`] describes the parts to send rather than holding open streams, so it can
+//! be rebuilt on every retry attempt. File parts are read from disk each time.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::http::Client;
+//! use xx::http::multipart::{Form, Part};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let form = Form::new()
+//!         .text("version", "1.2.3")
+//!         .file("asset", "dist/app.tar.gz")
+//!         .part(
+//!             "report",
+//!             Part::bytes(b"{}".to_vec())
+//!                 .file_name("report.json")
+//!                 .mime_str("application/json"),
+//!         );
+//!     let resp = Client::new()
+//!         .post_multipart("https://example.com/upload", form)
+//!         .await
+//!         .unwrap();
+//! }
+//! ```
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use reqwest::RequestBuilder;
+
+use super::body::RequestBody;
+use super::fixtures::Fnv1a;
+use crate::{XXError, XXResult, error};
+
+/// A multipart/form-data body
+#[derive(Debug, Clone, Default)]
+pub struct Form {
+    parts: Vec<(String, Part)>,
+}
+
+impl Form {
+    /// Create an empty form
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a text field
+    pub fn text<N: Into<String>, V: Into<String>>(self, name: N, value: V) -> Self {
+        self.part(name, Part::text(value))
+    }
+
+    /// Add a file read from disk
+    ///
+    /// The file name defaults to the last component of `path` and the content type
+    /// is guessed from its extension.
+    pub fn file<N: Into<String>, P: AsRef<Path>>(self, name: N, path: P) -> Self {
+        self.part(name, Part::file(path))
+    }
+
+    /// Add a part
+    pub fn part<N: Into<String>>(mut self, name: N, part: Part) -> Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    async fn build(&self) -> XXResult<reqwest::multipart::Form> {
+        let mut form = reqwest::multipart::Form::new();
+        for (name, part) in &self.parts {
+            form = form.part(name.clone(), part.build().await?);
+        }
+        Ok(form)
+    }
+}
+
+impl RequestBody for Form {
+    async fn attach(&self, request: RequestBuilder) -> XXResult<RequestBuilder> {
+        Ok(request.multipart(self.build().await?))
+    }
+
+    /// A hash of every part's name, settings and contents, so different uploads to one
+    /// URL are recorded separately
+    fn fixture_key(&self) -> XXResult<Cow<'_, [u8]>> {
+        let mut hash = Fnv1a::default();
+        for (name, part) in &self.parts {
+            hash.write(name.as_bytes());
+            match &part.source {
+                PartSource::Text(text) => {
+                    hash.write(b"text");
+                    hash.write(text.as_bytes());
+                }
+                PartSource::Bytes(bytes) => {
+                    hash.write(b"bytes");
+                    hash.write(bytes);
+                }
+                PartSource::File(path) => {
+                    hash.write(b"file");
+                    let file_name = path.file_name().unwrap_or_default();
+                    hash.write(file_name.as_encoded_bytes());
+                    hash.write_file(path)?;
+                }
+            }
+            hash.write(part.file_name.as_deref().unwrap_or_default().as_bytes());
+            hash.write(part.mime.as_deref().unwrap_or_default().as_bytes());
+        }
+        Ok(hash.0.to_le_bytes().to_vec().into())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PartSource {
+    Text(String),
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+/// A single field of a [`Form`]
+#[derive(Debug, Clone)]
+pub struct Part {
+    source: PartSource,
+    file_name: Option<String>,
+    mime: Option<String>,
+}
+
+impl Part {
+    fn new(source: PartSource) -> Self {
+        Self {
+            source,
+            file_name: None,
+            mime: None,
+        }
+    }
+
+    /// A text part
+    pub fn text<S: Into<String>>(value: S) -> Self {
+        Self::new(PartSource::Text(value.into()))
+    }
+
+    /// A part holding raw bytes
+    pub fn bytes<B: Into<Vec<u8>>>(value: B) -> Self {
+        Self::new(PartSource::Bytes(value.into()))
+    }
+
+    /// A part streamed from a file on disk
+    pub fn file<P: AsRef<Path>>(path: P) -> Self {
+        Self::new(PartSource::File(path.as_ref().to_path_buf()))
+    }
+
+    /// Set the file name sent for this part
+    pub fn file_name<S: Into<String>>(mut self, file_name: S) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Set the content type of this part, e.g. `application/gzip`
+    pub fn mime_str<S: Into<String>>(mut self, mime: S) -> Self {
+        self.mime = Some(mime.into());
+        self
+    }
+
+    async fn build(&self) -> XXResult<reqwest::multipart::Part> {
+        let mut part = match &self.source {
+            PartSource::Text(text) => reqwest::multipart::Part::text(text.clone()),
+            PartSource::Bytes(bytes) => reqwest::multipart::Part::bytes(bytes.clone()),
+            PartSource::File(path) => reqwest::multipart::Part::file(path)
+                .await
+                .map_err(|err| XXError::FileError(err, path.clone()))?,
+        };
+        if let Some(file_name) = &self.file_name {
+            part = part.file_name(file_name.clone());
+        }
+        if let Some(mime) = &self.mime {
+            part = part
+                .mime_str(mime)
+                .map_err(|err| error!("invalid content type {}: {}", mime, err))?;
+        }
+        Ok(part)
+    }
+}
@@ -0,0 +1,459 @@
+//! Following paginated API responses
+//!
+//! [`Paginator`] fetches one page at a time with [`Client::get`], so retries,
+//! authentication and caching apply to every page. [`Paginator::next_item`] yields
+//! the items of each page one by one, fetching the next page only when they run out.
+//! The next page is found from an RFC 8288 (formerly RFC 5988) `Link: <...>;
+//! rel="next"` header, or, for APIs that return a cursor in the JSON body, from a
+//! user-provided cursor extractor.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use xx::http::Client;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Release {
+//!     tag_name: String,
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> xx::XXResult<()> {
+//!     let client = Client::new().token_env("GITHUB_TOKEN", "api.github.com");
+//!
+//!     // Follow `Link` headers, deserializing each page as a JSON array
+//!     let releases: Vec<Release> = client
+//!         .paginate("https://api.github.com/repos/jdx/mise/releases?per_page=100")
+//!         .max_pages(5)
+//!         .items()
+//!         .await?;
+//!
+//!     // Cursor-based API: `{"data": [...], "next_cursor": "abc"}`
+//!     let mut pages = client
+//!         .paginate("https://api.example.com/items")
+//!         .cursor("cursor", |body| body["next_cursor"].as_str().map(String::from))
+//!         .items_pointer("/data");
+//!     while let Some(item) = pages.next_item::<serde_json::Value>().await? {
+//!         if item["name"] == "wanted" {
+//!             break; // no more pages are fetched
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+
+use super::{Client, XXHTTPResponse};
+use crate::{XXError, XXResult, error};
+
+type CursorExtractor = Arc<dyn Fn(&serde_json::Value) -> Option<String> + Send + Sync>;
+
+/// Iterates over the pages of a paginated API
+///
+/// Created with [`Client::paginate`].
+pub struct Paginator<'a> {
+    client: &'a Client,
+    base: Option<Url>,
+    next: Option<Url>,
+    error: Option<XXError>,
+    pages: usize,
+    /// Every URL fetched or redirected to, so a loop of `next` links ends
+    visited: HashSet<Url>,
+    /// Items of the current page not yet returned by `next_item`
+    buffered: VecDeque<serde_json::Value>,
+    max_pages: Option<usize>,
+    cursor: Option<(String, CursorExtractor)>,
+    items_pointer: Option<String>,
+}
+
+impl<'a> Paginator<'a> {
+    pub(crate) fn new(client: &'a Client, url: XXResult<Url>) -> Self {
+        let (url, error) = match url {
+            Ok(url) => (Some(url), None),
+            Err(err) => (None, Some(err)),
+        };
+        Self {
+            client,
+            base: url.clone(),
+            next: url,
+            error,
+            pages: 0,
+            visited: HashSet::new(),
+            buffered: VecDeque::new(),
+            max_pages: None,
+            cursor: None,
+            items_pointer: None,
+        }
+    }
+
+    /// Stop after fetching this many pages
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    /// Find the next page from a cursor in the JSON body
+    ///
+    /// `extract` is called with each page's parsed body. When it returns a cursor, the
+    /// next page is the original URL with the `param` query parameter set to it. This is
+    /// only used when the response has no `Link: rel="next"` header.
+    pub fn cursor<S, F>(mut self, param: S, extract: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&serde_json::Value) -> Option<String> + Send + Sync + 'static,
+    {
+        self.cursor = Some((param.into(), Arc::new(extract)));
+        self
+    }
+
+    /// Read [`Paginator::items`] from a JSON pointer such as `/data` instead of the
+    /// top level of each page
+    pub fn items_pointer<S: Into<String>>(mut self, pointer: S) -> Self {
+        self.items_pointer = Some(pointer.into());
+        self
+    }
+
+    /// Fetch the next page, or `None` once there are no more pages
+    ///
+    /// Items of the current page that [`Paginator::next_item`] hasn't returned yet are
+    /// skipped.
+    pub async fn next_page(&mut self) -> XXResult<Option<XXHTTPResponse>> {
+        self.buffered.clear();
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if self.max_pages.is_some_and(|max| self.pages >= max) {
+            trace!("stopping pagination after {} pages", self.pages);
+            return Ok(None);
+        }
+        let Some(url) = self.next.take() else {
+            return Ok(None);
+        };
+
+        let resp = self.client.get(url.clone()).await?;
+        self.pages += 1;
+        self.visited.insert(url);
+        self.visited.extend(resp.redirects.iter().cloned());
+        self.visited.insert(resp.url.clone());
+        self.next = self.next_url(&resp);
+        match &self.next {
+            Some(next) if self.visited.contains(next) => {
+                debug!("stopping pagination: {} was already fetched", next);
+                self.next = None;
+            }
+            Some(next) => trace!("next page: {}", next),
+            None => {}
+        }
+        Ok(Some(resp))
+    }
+
+    /// Return the next JSON array item, fetching the next page when the current one
+    /// runs out, or `None` once there are no more items
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use xx::http::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> xx::XXResult<()> {
+    ///     let client = Client::new();
+    ///     let mut tags = client.paginate("https://api.github.com/repos/jdx/xx/tags");
+    ///     while let Some(tag) = tags.next_item::<serde_json::Value>().await? {
+    ///         println!("{}", tag["name"]);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn next_item<T: DeserializeOwned>(&mut self) -> XXResult<Option<T>> {
+        loop {
+            if let Some(item) = self.buffered.pop_front() {
+                return serde_json::from_value(item)
+                    .map(Some)
+                    .map_err(|err| error!("JSON parse error: {}", err));
+            }
+            let Some(page) = self.next_page().await? else {
+                return Ok(None);
+            };
+            self.buffered = self.page_items(&page)?.into();
+        }
+    }
+
+    /// Fetch all remaining pages and collect the JSON array items from each one
+    pub async fn items<T: DeserializeOwned>(mut self) -> XXResult<Vec<T>> {
+        let mut items = vec![];
+        while let Some(item) = self.next_item().await? {
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    /// The JSON array items of a page, read from `items_pointer` if set
+    fn page_items(&self, page: &XXHTTPResponse) -> XXResult<Vec<serde_json::Value>> {
+        let mut body: serde_json::Value = page.json()?;
+        if let Some(pointer) = &self.items_pointer {
+            body = body
+                .pointer_mut(pointer)
+                .map(serde_json::Value::take)
+                .ok_or_else(|| error!("no items at {} in paginated response", pointer))?;
+        }
+        serde_json::from_value(body).map_err(|err| error!("JSON parse error: {}", err))
+    }
+
+    fn next_url(&self, resp: &XXHTTPResponse) -> Option<Url> {
+        let link = resp
+            .headers
+            .get_all(reqwest::header::LINK)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(find_next_link);
+        if let Some(link) = link {
+            // Relative links are relative to where the page came from, after redirects
+            return resp.url.join(&link).ok();
+        }
+
+        let (param, extract) = self.cursor.as_ref()?;
+        let body = resp.json::<serde_json::Value>().ok()?;
+        let cursor = extract(&body).filter(|c| !c.is_empty())?;
+        let mut next = self.base.clone()?;
+        let pairs: Vec<(String, String)> = next
+            .query_pairs()
+            .filter(|(k, _)| k != param.as_str())
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        next.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair(param, &cursor);
+        Some(next)
+    }
+}
+
+/// Find the target of the `rel="next"` link in a `Link` header value
+fn find_next_link(value: &str) -> Option<String> {
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>')?;
+        let target = &rest[start + 1..end];
+        let params_end = rest[end..].find('<').map_or(rest.len(), |i| end + i);
+        let is_next = rest[end + 1..params_end]
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .flat_map(|(_, rels)| {
+                rels.trim()
+                    .trim_end_matches(',')
+                    .trim_matches('"')
+                    .split(' ')
+            })
+            .any(|rel| rel.eq_ignore_ascii_case("next"));
+        if is_next {
+            return Some(target.to_string());
+        }
+        rest = &rest[params_end..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path, query_param},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_find_next_link() {
+        assert_eq!(
+            find_next_link(
+                r#"<https://api.github.com/repos/a/b/releases?page=2>; rel="next", <https://api.github.com/repos/a/b/releases?page=5>; rel="last""#
+            ),
+            Some("https://api.github.com/repos/a/b/releases?page=2".into())
+        );
+        assert_eq!(
+            find_next_link(r#"</items?page=1>; rel="prev first", </items?page=3>; rel=next"#),
+            Some("/items?page=3".into())
+        );
+        assert_eq!(find_next_link(r#"</items?page=1>; rel="prev""#), None);
+        assert_eq!(find_next_link("garbage"), None);
+    }
+
+    #[test(tokio::test)]
+    async fn test_paginate_link_header() {
+        let mock_server = MockServer::start().await;
+        for page in 1..=3 {
+            let mut resp = ResponseTemplate::new(200).set_body_json(vec![page * 10, page * 10 + 1]);
+            if page < 3 {
+                resp = resp.insert_header(
+                    "Link",
+                    format!(
+                        r#"</items?page={}>; rel="next", </items?page=3>; rel="last""#,
+                        page + 1
+                    ),
+                );
+            }
+            Mock::given(method("GET"))
+                .and(path("/items"))
+                .and(query_param("page", page.to_string()))
+                .respond_with(resp)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let client = Client::new();
+        let url = format!("{}/items?page=1", mock_server.uri());
+        let items: Vec<u32> = client.paginate(&url).items().await.unwrap();
+        assert_eq!(items, vec![10, 11, 20, 21, 30, 31]);
+
+        let items: Vec<u32> = client.paginate(&url).max_pages(2).items().await.unwrap();
+        assert_eq!(items, vec![10, 11, 20, 21]);
+    }
+
+    #[test(tokio::test)]
+    async fn test_paginate_cursor() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .and(|req: &wiremock::Request| !req.url.query_pairs().any(|(k, _)| k == "after"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"data": ["a", "b"], "next": "c1"})),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .and(query_param("after", "c1"))
+            .and(query_param("limit", "2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"data": ["c"], "next": null})),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let mut pages = client
+            .paginate(format!("{}/items?limit=2", mock_server.uri()))
+            .cursor("after", |body| body["next"].as_str().map(String::from))
+            .items_pointer("/data");
+        assert!(pages.next_page().await.unwrap().is_some());
+        assert!(pages.next_page().await.unwrap().is_some());
+        assert!(pages.next_page().await.unwrap().is_none());
+
+        let items: Vec<String> = client
+            .paginate(format!("{}/items?limit=2", mock_server.uri()))
+            .cursor("after", |body| body["next"].as_str().map(String::from))
+            .items_pointer("/data")
+            .items()
+            .await
+            .unwrap();
+        assert_eq!(items, vec!["a", "b", "c"]);
+    }
+
+    #[test(tokio::test)]
+    async fn test_paginate_next_item() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .and(query_param("page", "1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(vec![1, 2])
+                    .insert_header("Link", r#"</items?page=2>; rel="next""#),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![3]))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let mut pages = client.paginate(format!("{}/items?page=1", mock_server.uri()));
+        assert_eq!(pages.next_item::<u32>().await.unwrap(), Some(1));
+        assert_eq!(pages.next_item::<u32>().await.unwrap(), Some(2));
+        // The second page is only fetched once the first one runs out
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+        assert_eq!(pages.next_item::<u32>().await.unwrap(), Some(3));
+        assert_eq!(pages.next_item::<u32>().await.unwrap(), None);
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[test(tokio::test)]
+    async fn test_paginate_relative_link_after_redirect() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/v2/items"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/items"))
+            .and(|req: &wiremock::Request| req.url.query().is_none())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(vec![1])
+                    .insert_header("Link", r#"<items?page=2>; rel="next""#),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/items"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![2]))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let items: Vec<u32> = client
+            .paginate(format!("{}/items", mock_server.uri()))
+            .items()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    #[test(tokio::test)]
+    async fn test_paginate_link_loop() {
+        let mock_server = MockServer::start().await;
+        for (page, next) in [("a", "b"), ("b", "a")] {
+            Mock::given(method("GET"))
+                .and(path(format!("/{page}")))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(vec![page])
+                        .insert_header("Link", format!(r#"</{next}>; rel="next""#)),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let client = Client::new();
+        let items: Vec<String> = client
+            .paginate(format!("{}/a", mock_server.uri()))
+            .items()
+            .await
+            .unwrap();
+        assert_eq!(items, vec!["a", "b"]);
+    }
+
+    #[test(tokio::test)]
+    async fn test_paginate_invalid_url() {
+        let client = Client::new();
+        let mut pages = client.paginate("not a url");
+        assert!(pages.next_page().await.is_err());
+    }
+}
@@ -0,0 +1,410 @@
+//! Proxy selection for the HTTP client
+//!
+//! Proxies can be set explicitly on [`Client`](super::Client) or picked up from the
+//! conventional `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment
+//! variables (lowercase names take precedence). Explicit settings always win over the
+//! environment, and scheme-specific proxies win over `ALL_PROXY`. Like reqwest and
+//! curl, uppercase `HTTP_PROXY` is ignored when `REQUEST_METHOD` is set, since a CGI
+//! script gets it from the client's `Proxy:` request header ("httpoxy").
+//!
+//! `NO_PROXY` entries are comma-separated and may be:
+//!
+//! - `*` to bypass the proxy for every host
+//! - a domain such as `example.com` or `.example.com`, matching it and all subdomains
+//! - an IP address such as `10.1.2.3` or `::1`
+//! - a CIDR block such as `10.0.0.0/8` or `fd00::/8`
+//!
+//! Domain and IP entries may carry a `:port` suffix to only match that port.
+
+use std::net::IpAddr;
+
+use reqwest::Url;
+
+use crate::{XXResult, error};
+
+/// Proxy settings configured on a [`Client`](super::Client)
+#[derive(Clone)]
+pub(crate) struct ProxyConfig {
+    pub(crate) http: Option<String>,
+    pub(crate) https: Option<String>,
+    pub(crate) all: Option<String>,
+    pub(crate) auth: Option<(String, String)>,
+    pub(crate) no_proxy: Vec<String>,
+    pub(crate) use_env: bool,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            http: None,
+            https: None,
+            all: None,
+            auth: None,
+            no_proxy: vec![],
+            use_env: true,
+        }
+    }
+}
+
+impl ProxyConfig {
+    /// Build a reqwest proxy that routes each request according to these settings
+    pub(crate) fn build(&self) -> XXResult<reqwest::Proxy> {
+        let resolver = self.resolver(|name| std::env::var(name).ok())?;
+        let mut proxy = reqwest::Proxy::custom(move |url| resolver.resolve(url));
+        if let Some((username, password)) = &self.auth {
+            proxy = proxy.basic_auth(username, password);
+        }
+        Ok(proxy)
+    }
+
+    /// Resolve the settings against an environment, validating explicit proxy URLs
+    pub(crate) fn resolver<F>(&self, env: F) -> XXResult<ProxyResolver>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let explicit = |value: &Option<String>, source: &str| -> XXResult<Option<ProxyTarget>> {
+            value
+                .as_deref()
+                .map(|v| ProxyTarget::parse(v, source))
+                .transpose()
+        };
+        let from_env = |name: &str| -> Option<ProxyTarget> {
+            if !self.use_env {
+                return None;
+            }
+            let cgi = env("REQUEST_METHOD").is_some();
+            let (var, value) = [name.to_lowercase(), name.to_string()]
+                .into_iter()
+                .filter(|var| !(cgi && var == "HTTP_PROXY"))
+                .find_map(|var| env(&var).filter(|v| !v.is_empty()).map(|v| (var, v)))?;
+            match ProxyTarget::parse(&value, &var) {
+                Ok(target) => Some(target),
+                Err(err) => {
+                    warn!("ignoring {}: {}", var, err);
+                    None
+                }
+            }
+        };
+
+        let explicit_all = explicit(&self.all, "Client::proxy")?;
+        let http = explicit(&self.http, "Client::http_proxy")?
+            .or_else(|| explicit_all.clone())
+            .or_else(|| from_env("HTTP_PROXY"))
+            .or_else(|| from_env("ALL_PROXY"));
+        let https = explicit(&self.https, "Client::https_proxy")?
+            .or(explicit_all)
+            .or_else(|| from_env("HTTPS_PROXY"))
+            .or_else(|| from_env("ALL_PROXY"));
+
+        let mut no_proxy = split_no_proxy(self.no_proxy.iter().map(|s| s.as_str()));
+        if self.use_env
+            && let Some(value) = env("no_proxy").or_else(|| env("NO_PROXY"))
+        {
+            no_proxy.extend(split_no_proxy([value.as_str()]));
+        }
+
+        Ok(ProxyResolver {
+            http,
+            https,
+            no_proxy,
+        })
+    }
+}
+
+/// A proxy URL along with where it was configured, for logging
+#[derive(Clone, Debug)]
+struct ProxyTarget {
+    url: Url,
+    source: String,
+}
+
+impl ProxyTarget {
+    fn parse(value: &str, source: &str) -> XXResult<Self> {
+        // Like curl, treat a bare `host:port` as an HTTP proxy
+        let value = if value.contains("://") {
+            value.to_string()
+        } else {
+            format!("http://{value}")
+        };
+        let url =
+            Url::parse(&value).map_err(|err| error!("invalid proxy url {}: {}", value, err))?;
+        Ok(Self {
+            url,
+            source: source.to_string(),
+        })
+    }
+}
+
+/// Proxy settings resolved against the environment
+pub(crate) struct ProxyResolver {
+    http: Option<ProxyTarget>,
+    https: Option<ProxyTarget>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyResolver {
+    /// Pick the proxy to use for a request URL, or `None` to connect directly
+    pub(crate) fn resolve(&self, url: &Url) -> Option<Url> {
+        let target = match url.scheme() {
+            "http" => self.http.as_ref(),
+            "https" => self.https.as_ref(),
+            _ => None,
+        };
+        let Some(target) = target else {
+            trace!("proxy for {}: none configured", url);
+            return None;
+        };
+        if let Some(entry) = self.no_proxy.iter().find(|e| no_proxy_matches(e, url)) {
+            trace!("proxy for {}: bypassed by NO_PROXY entry {}", url, entry);
+            return None;
+        }
+        trace!(
+            "proxy for {}: {} (from {})",
+            url,
+            redact(&target.url),
+            target.source
+        );
+        Some(target.url.clone())
+    }
+}
+
+/// Strip credentials from a proxy URL so it can be logged
+fn redact(url: &Url) -> String {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("***"));
+    }
+    url.to_string()
+}
+
+fn split_no_proxy<'a>(values: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    values
+        .into_iter()
+        .flat_map(|v| v.split(','))
+        .map(|e| e.trim().to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+/// Check whether a single `NO_PROXY` entry matches a request URL
+fn no_proxy_matches(entry: &str, url: &Url) -> bool {
+    if entry == "*" {
+        return true;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host_ip = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok();
+
+    // CIDR block
+    if let Some((net, prefix)) = entry.split_once('/') {
+        let net = net.trim_start_matches('[').trim_end_matches(']');
+        return match (host_ip, net.parse::<IpAddr>(), prefix.parse::<u32>()) {
+            (Some(ip), Ok(net), Ok(prefix)) => ip_in_cidr(ip, net, prefix),
+            _ => false,
+        };
+    }
+
+    let (entry, port) = split_port(entry);
+    if port.is_some() && url.port_or_known_default() != port {
+        return false;
+    }
+
+    if let Ok(ip) = entry.parse::<IpAddr>() {
+        return host_ip == Some(ip);
+    }
+    if host_ip.is_some() {
+        return false;
+    }
+
+    let domain = host.trim_end_matches('.').to_lowercase();
+    let entry = entry.trim_start_matches('*').trim_start_matches('.');
+    domain == entry || domain.ends_with(&format!(".{entry}"))
+}
+
+/// Split an optional `:port` suffix off a `NO_PROXY` entry
+fn split_port(entry: &str) -> (&str, Option<u16>) {
+    if let Some(rest) = entry.strip_prefix('[') {
+        // Bracketed IPv6, e.g. `[::1]:8080`
+        return match rest.split_once(']') {
+            Some((ip, port)) => (ip, port.strip_prefix(':').and_then(|p| p.parse().ok())),
+            None => (entry, None),
+        };
+    }
+    if entry.matches(':').count() == 1
+        && let Some((host, port)) = entry.split_once(':')
+        && let Ok(port) = port.parse()
+    {
+        return (host, Some(port));
+    }
+    (entry, None)
+}
+
+fn ip_in_cidr(ip: IpAddr, net: IpAddr, prefix: u32) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn resolve(config: &ProxyConfig, env: &[(&str, &str)], url: &str) -> Option<String> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        config
+            .resolver(|name| env.get(name).cloned())
+            .unwrap()
+            .resolve(&Url::parse(url).unwrap())
+            .map(|u| u.to_string())
+    }
+
+    #[test]
+    fn test_explicit_proxies() {
+        let config = ProxyConfig {
+            http: Some("http://http-proxy:3128".into()),
+            all: Some("socks5://all-proxy:1080".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve(&config, &[], "http://example.com"),
+            Some("http://http-proxy:3128/".into())
+        );
+        assert_eq!(
+            resolve(&config, &[], "https://example.com"),
+            Some("socks5://all-proxy:1080".into())
+        );
+    }
+
+    #[test]
+    fn test_env_proxies() {
+        let env = [
+            ("HTTPS_PROXY", "http://upper:1"),
+            ("https_proxy", "http://lower:1"),
+            ("ALL_PROXY", "other:8080"),
+        ];
+        let config = ProxyConfig::default();
+        assert_eq!(
+            resolve(&config, &env, "https://example.com"),
+            Some("http://lower:1/".into())
+        );
+        assert_eq!(
+            resolve(&config, &env, "http://example.com"),
+            Some("http://other:8080/".into())
+        );
+
+        // A CGI client can set HTTP_PROXY with a Proxy header, so it isn't trusted there
+        let cgi = [
+            ("REQUEST_METHOD", "GET"),
+            ("HTTP_PROXY", "http://attacker:1"),
+        ];
+        assert_eq!(resolve(&config, &cgi, "http://example.com"), None);
+        let cgi = [
+            ("REQUEST_METHOD", "GET"),
+            ("HTTP_PROXY", "http://attacker:1"),
+            ("http_proxy", "http://lower:1"),
+        ];
+        assert_eq!(
+            resolve(&config, &cgi, "http://example.com"),
+            Some("http://lower:1/".into())
+        );
+        assert_eq!(
+            resolve(
+                &config,
+                &[("HTTP_PROXY", "http://upper:1")],
+                "http://example.com"
+            ),
+            Some("http://upper:1/".into())
+        );
+
+        // Explicit settings win over the environment
+        let config = ProxyConfig {
+            https: Some("http://explicit:1".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve(&config, &env, "https://example.com"),
+            Some("http://explicit:1/".into())
+        );
+
+        // The environment can be ignored entirely
+        let config = ProxyConfig {
+            use_env: false,
+            ..Default::default()
+        };
+        assert_eq!(resolve(&config, &env, "https://example.com"), None);
+    }
+
+    #[test]
+    fn test_no_proxy() {
+        let env = [
+            ("ALL_PROXY", "http://proxy:3128"),
+            (
+                "NO_PROXY",
+                "internal.corp, 10.0.0.0/8,192.168.1.5,[::1]:8080",
+            ),
+        ];
+        let config = ProxyConfig {
+            no_proxy: vec!["*.example.org".into()],
+            ..Default::default()
+        };
+        let direct = [
+            "http://internal.corp/",
+            "https://build.internal.corp/",
+            "http://10.20.30.40/",
+            "http://192.168.1.5/",
+            "http://[::1]:8080/",
+            "https://example.org/",
+            "https://api.example.org/",
+        ];
+        for url in direct {
+            assert_eq!(resolve(&config, &env, url), None, "{url}");
+        }
+        let proxied = [
+            "http://notinternal.corp/",
+            "http://11.0.0.1/",
+            "http://192.168.1.6/",
+            "http://[::1]:9090/",
+            "https://example.com/",
+        ];
+        for url in proxied {
+            assert!(resolve(&config, &env, url).is_some(), "{url}");
+        }
+
+        let env = [("ALL_PROXY", "http://proxy:3128"), ("no_proxy", "*")];
+        assert_eq!(resolve(&config, &env, "https://example.com"), None);
+    }
+
+    #[test]
+    fn test_invalid_proxy() {
+        let config = ProxyConfig {
+            http: Some("http://[bad".into()),
+            ..Default::default()
+        };
+        assert!(config.resolver(|_| None).is_err());
+
+        // Invalid environment values are ignored rather than failing every request
+        let config = ProxyConfig::default();
+        assert_eq!(
+            resolve(&config, &[("HTTP_PROXY", "http://[bad")], "http://a.com"),
+            None
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Redirect handling for the HTTP client
+//!
+//! Redirects are followed by the client itself rather than by reqwest so that every
+//! hop is visible: the final URL and the chain of URLs that led to it end up on
+//! [`XXHTTPResponse`](super::XXHTTPResponse). Credentials are only sent to hops with
+//! the same scheme, host and port as the original request: authentication, and
+//! headers such as `Authorization`, `Cookie` or `X-API-Key` set with
+//! [`Client::header`](super::Client::header), are dropped on other hops.
+//!
+//! As in browsers, `303 See Other` (and `301`/`302` in response to a `POST`) switch
+//! the request to a `GET` without a body; `307` and `308` resend the same request.
+
+use reqwest::{Method, StatusCode, Url};
+
+use crate::{XXResult, error};
+
+/// Default maximum number of redirects to follow
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Redirect settings configured on a [`Client`](super::Client)
+#[derive(Clone)]
+pub(crate) struct RedirectConfig {
+    pub(crate) limit: usize,
+    pub(crate) allowed_hosts: Vec<String>,
+}
+
+impl Default for RedirectConfig {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_MAX_REDIRECTS,
+            allowed_hosts: vec![],
+        }
+    }
+}
+
+/// The URLs that were redirected from, attached to the final response
+#[derive(Clone, Default)]
+pub(crate) struct RedirectChain(pub(crate) Vec<Url>);
+
+impl RedirectConfig {
+    /// Decide where to go next, if `resp` is a redirect that should be followed
+    ///
+    /// `origin` is the URL of the original request and `followed` is the number of
+    /// redirects followed so far.
+    pub(crate) fn next(
+        &self,
+        origin: &Url,
+        resp: &reqwest::Response,
+        followed: usize,
+    ) -> XXResult<Option<Url>> {
+        if self.limit == 0 || !is_redirect(resp.status()) {
+            return Ok(None);
+        }
+        let Some(location) = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|l| l.to_str().ok())
+        else {
+            return Ok(None);
+        };
+        let next = resp
+            .url()
+            .join(location)
+            .map_err(|err| error!("invalid redirect location {}: {}", location, err))?;
+        if followed >= self.limit {
+            return Err(error!(
+                "too many redirects for {} (limit: {})",
+                origin, self.limit
+            ));
+        }
+        if !matches!(next.scheme(), "http" | "https") {
+            return Err(error!("refusing to redirect to {}", next));
+        }
+        if !self.is_allowed(origin, &next) {
+            return Err(error!(
+                "redirect from {} to {} is not allowed: {} is not in the redirect host allow-list",
+                resp.url(),
+                next,
+                next.host_str().unwrap_or_default()
+            ));
+        }
+        trace!("following {} redirect to {}", resp.status(), next);
+        Ok(Some(next))
+    }
+
+    fn is_allowed(&self, origin: &Url, next: &Url) -> bool {
+        if self.allowed_hosts.is_empty() {
+            return true;
+        }
+        let Some(host) = next.host_str().map(|h| h.to_lowercase()) else {
+            return false;
+        };
+        if origin.host_str() == Some(host.as_str()) {
+            return true;
+        }
+        self.allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_lowercase();
+            match allowed.strip_prefix('.').or(allowed.strip_prefix("*.")) {
+                Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
+                None => host == allowed,
+            }
+        })
+    }
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+/// Whether following a redirect turns the request into a bodyless `GET`
+pub(crate) fn switches_to_get(status: StatusCode, method: &Method) -> bool {
+    match status {
+        StatusCode::SEE_OTHER => *method != Method::HEAD,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => *method == Method::POST,
+        _ => false,
+    }
+}
+
+/// Whether two URLs share a scheme, host and port
+pub(crate) fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let config = RedirectConfig {
+            allowed_hosts: vec![
+                "objects.githubusercontent.com".into(),
+                ".amazonaws.com".into(),
+            ],
+            ..Default::default()
+        };
+        let origin = url("https://github.com/jdx/mise/releases/download/v1/mise.tar.gz");
+        for allowed in [
+            "https://github.com/other",
+            "https://objects.githubusercontent.com/x",
+            "https://amazonaws.com/x",
+            "https://bucket.s3.amazonaws.com/x",
+        ] {
+            assert!(config.is_allowed(&origin, &url(allowed)), "{allowed}");
+        }
+        for denied in [
+            "https://evil.com/x",
+            "https://githubusercontent.com/x",
+            "https://notamazonaws.com/x",
+        ] {
+            assert!(!config.is_allowed(&origin, &url(denied)), "{denied}");
+        }
+        assert!(RedirectConfig::default().is_allowed(&origin, &url("https://evil.com")));
+    }
+
+    #[test]
+    fn test_switches_to_get() {
+        assert!(switches_to_get(StatusCode::SEE_OTHER, &Method::PUT));
+        assert!(switches_to_get(StatusCode::FOUND, &Method::POST));
+        assert!(!switches_to_get(StatusCode::FOUND, &Method::PUT));
+        assert!(!switches_to_get(
+            StatusCode::TEMPORARY_REDIRECT,
+            &Method::POST
+        ));
+        assert!(!switches_to_get(
+            StatusCode::PERMANENT_REDIRECT,
+            &Method::POST
+        ));
+    }
+
+    #[test]
+    fn test_same_origin() {
+        assert!(same_origin(
+            &url("https://a.com/x"),
+            &url("https://a.com:443/y")
+        ));
+        assert!(!same_origin(&url("https://a.com"), &url("http://a.com")));
+        assert!(!same_origin(
+            &url("http://a.com:8080"),
+            &url("http://a.com:8081")
+        ));
+        assert!(!same_origin(&url("https://a.com"), &url("https://b.a.com")));
+    }
+}
@@ -0,0 +1,191 @@
+//! HTTP responses
+//!
+//! The body is kept as raw bytes so binary payloads (protobuf, tarballs) survive
+//! untouched. [`XXHTTPResponse::text`] decodes it on first use according to the
+//! `charset` parameter of the `Content-Type` header, defaulting to UTF-8.
+
+use std::sync::OnceLock;
+
+use bytes::Bytes;
+use reqwest::Url;
+use reqwest::header::HeaderMap;
+use serde::de::DeserializeOwned;
+
+use super::redirect::RedirectChain;
+use crate::{XXError, XXResult, error};
+
+/// HTTP response
+#[derive(Debug)]
+pub struct XXHTTPResponse {
+    /// HTTP status code
+    pub status: reqwest::StatusCode,
+    /// Response headers
+    pub headers: HeaderMap,
+    /// Raw response body
+    pub body: Bytes,
+    /// The URL the response came from, after following redirects
+    pub url: Url,
+    /// The URLs that redirected to [`url`](Self::url), in order, starting with the
+    /// requested URL. Empty if there were no redirects.
+    pub redirects: Vec<Url>,
+    text: OnceLock<String>,
+}
+
+impl XXHTTPResponse {
+    pub(crate) fn new(
+        url: Url,
+        status: reqwest::StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+            url,
+            redirects: vec![],
+            text: OnceLock::new(),
+        }
+    }
+
+    /// Read the full body of a reqwest response
+    pub(crate) async fn read(resp: reqwest::Response, url: String) -> XXResult<Self> {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let final_url = resp.url().clone();
+        let redirects = resp
+            .extensions()
+            .get::<RedirectChain>()
+            .map(|chain| chain.0.clone())
+            .unwrap_or_default();
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|err| XXError::HTTPError(err, url))?;
+        Ok(Self {
+            redirects,
+            ..Self::new(final_url, status, headers, body)
+        })
+    }
+
+    /// The raw response body
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The response body decoded as text
+    ///
+    /// Uses the `charset` from the `Content-Type` header. UTF-8 and ASCII are decoded
+    /// lossily, ISO-8859-1 is mapped byte for byte, and unknown charsets fall back to
+    /// lossy UTF-8. The decoded text is cached after the first call.
+    pub fn text(&self) -> &str {
+        self.text.get_or_init(|| decode(&self.body, self.charset()))
+    }
+
+    /// Deserialize the response body as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> XXResult<T> {
+        serde_json::from_slice(&self.body).map_err(|err| error!("JSON parse error: {}", err))
+    }
+
+    /// The `charset` parameter of the `Content-Type` header, lowercased
+    pub fn charset(&self) -> Option<String> {
+        let content_type = self
+            .headers
+            .get(reqwest::header::CONTENT_TYPE)?
+            .to_str()
+            .ok()?;
+        content_type.split(';').skip(1).find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("charset")
+                .then(|| value.trim().trim_matches('"').to_ascii_lowercase())
+        })
+    }
+}
+
+/// A body stored on disk (HTTP cache, fixtures)
+///
+/// Stored as text when it's valid UTF-8 to keep the files readable.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub(crate) enum StoredBody {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl From<&Bytes> for StoredBody {
+    fn from(body: &Bytes) -> Self {
+        match std::str::from_utf8(body) {
+            Ok(text) => Self::Text(text.to_string()),
+            Err(_) => Self::Bytes(body.to_vec()),
+        }
+    }
+}
+
+impl From<StoredBody> for Bytes {
+    fn from(body: StoredBody) -> Self {
+        match body {
+            StoredBody::Text(text) => text.into(),
+            StoredBody::Bytes(bytes) => bytes.into(),
+        }
+    }
+}
+
+fn decode(body: &[u8], charset: Option<String>) -> String {
+    match charset.as_deref() {
+        None | Some("utf-8" | "utf8" | "us-ascii" | "ascii") => {
+            let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+            String::from_utf8_lossy(body).into_owned()
+        }
+        Some("iso-8859-1" | "latin1" | "latin-1" | "l1") => {
+            body.iter().map(|&b| b as char).collect()
+        }
+        Some(charset) => {
+            trace!("unsupported charset {}, decoding as UTF-8", charset);
+            String::from_utf8_lossy(body).into_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn response(content_type: Option<&str>, body: &'static [u8]) -> XXHTTPResponse {
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = content_type {
+            headers.insert(reqwest::header::CONTENT_TYPE, content_type.parse().unwrap());
+        }
+        XXHTTPResponse::new(
+            Url::parse("https://example.com").unwrap(),
+            reqwest::StatusCode::OK,
+            headers,
+            Bytes::from_static(body),
+        )
+    }
+
+    #[test]
+    fn test_text_charset() {
+        let resp = response(Some("text/plain"), b"\xEF\xBB\xBFcaf\xC3\xA9");
+        assert_eq!(resp.charset(), None);
+        assert_eq!(resp.text(), "café");
+
+        let resp = response(Some("text/plain; Charset=\"ISO-8859-1\""), b"caf\xE9");
+        assert_eq!(resp.charset().as_deref(), Some("iso-8859-1"));
+        assert_eq!(resp.text(), "café");
+
+        let resp = response(None, b"\x00\xFFbinary");
+        assert_eq!(resp.bytes(), b"\x00\xFFbinary");
+        assert_eq!(resp.text(), "\0\u{FFFD}binary");
+    }
+
+    #[test]
+    fn test_json() {
+        let resp = response(Some("application/json"), br#"{"a": 1}"#);
+        let value: serde_json::Value = resp.json().unwrap();
+        assert_eq!(value["a"], 1);
+        assert!(resp.json::<Vec<u8>>().is_err());
+    }
+}
@@ -37,6 +37,8 @@
 //! - `hash` - SHA256 hashing utilities
 //! - `http` - HTTP client functionality
 //! - `fslock` - File system locking
+//! - `process_async` - Async process execution on tokio (`run_async`, `read_async`)
+//! - `process_unix` - Signals, signal forwarding and pseudo-terminals for processes on Unix
 //!
 //! ## Examples
 //!
@@ -67,6 +69,9 @@
 //! // Run shell command
 //! let output = process::sh("ls -la")?;
 //!
+//! // Interpolate arguments into a shell script with safe quoting
+//! let listing = xx::sh!("ls {}", "My Documents")?;
+//!
 //! // Build and run commands with builder pattern
 //! let result = process::cmd("git", &["status"]).read()?;
 //! # Ok(())
@@ -99,6 +104,8 @@ extern crate log;
 #[macro_use]
 pub use error::{XXError, XXResult};
 
+#[cfg(any(feature = "http", not(target_family = "wasm")))]
+mod backoff;
 /// Cache management utilities (requires `cache` feature)
 #[cfg(feature = "cache")]
 pub mod cache;
@@ -124,6 +131,8 @@ pub mod platform;
 pub mod process;
 /// Random generation utilities
 pub mod rand;
+#[cfg(any(feature = "http", not(target_family = "wasm")))]
+mod redact;
 mod regex;
 /// String similarity and suggestion utilities
 pub mod suggest;
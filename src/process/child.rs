@@ -0,0 +1,391 @@
+//! Handles for processes that keep running in the background
+//!
+//! [`XXExpression::spawn`] starts a command and returns an [`XXChild`] right away,
+//! for long-running children such as dev servers: write to its stdin as needed,
+//! follow its output with [`on_stdout_line`](XXExpression::on_stdout_line) and
+//! [`on_stderr_line`](XXExpression::on_stderr_line) handlers, then stop it with
+//! [`XXChild::shutdown`] or wait for it to finish with [`XXChild::wait`].
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use std::io::Write;
+//! use xx::process;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! let mut server = process::cmd("python3", ["-m", "http.server", "8000"])
+//!     .on_stderr_line(|line| println!("server: {line}"))
+//!     .process_group()
+//!     .spawn()?;
+//! // ... make requests ...
+//! server.shutdown()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Write;
+use std::process::{Child, ChildStdin, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::group::GroupGuard;
+use super::supervise::{self, Watchdog};
+use super::{StreamedOutput, XXExpression, join_output, process_failure, read_lines};
+use crate::{XXError, XXResult};
+
+/// How often [`XXChild::shutdown`] checks whether the process has exited
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A running process started with [`XXExpression::spawn`]
+///
+/// Dropping the handle leaves the process running, like `std::process::Child`, unless
+/// it was started with [`process_group`](XXExpression::process_group), in which case
+/// its group is killed.
+pub struct XXChild {
+    command: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdin_handle: Option<thread::JoinHandle<()>>,
+    stdout_handle: Option<thread::JoinHandle<StreamedOutput>>,
+    stderr_handle: Option<thread::JoinHandle<StreamedOutput>>,
+    watchdog: Option<Watchdog>,
+    guard: Option<GroupGuard>,
+    own_group: bool,
+    unchecked: bool,
+    grace_period: Duration,
+}
+
+impl XXExpression {
+    /// Start the command without waiting for it to finish
+    ///
+    /// Stdin is piped: write to it with [`XXChild::stdin`], or set it up front with
+    /// [`stdin_bytes`](Self::stdin_bytes). Output is captured, redirected or passed to
+    /// line handlers as with [`run`](Self::run), and timeouts and cancel handles apply.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use xx::process;
+    /// let mut child = process::cmd("tr", ["a-z", "A-Z"]).stdout_capture().spawn().unwrap();
+    /// child.stdin().unwrap().write_all(b"hello\n").unwrap();
+    /// let output = child.wait().unwrap();
+    /// assert_eq!(output.stdout, b"HELLO\n");
+    /// ```
+    pub fn spawn(&self) -> XXResult<XXChild> {
+        debug!("$ {self} &");
+        let mut cmd = self.command();
+        let output = self.output_stdio(self.stdout_capture)?;
+        cmd.stdin(Stdio::piped())
+            .stdout(output.stdout)
+            .stderr(output.stderr);
+        let mut child = cmd
+            .spawn()
+            .map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        // Close our copies of the merged pipe's write end so reading it can finish
+        drop(cmd);
+        let pid = child.id();
+
+        let mut stdin = child.stdin.take();
+        let stdin_handle = self.stdin_data.clone().and_then(|data| {
+            stdin.take().map(|mut stdin| {
+                thread::spawn(move || {
+                    let _ = stdin.write_all(&data);
+                })
+            })
+        });
+        let capture_stdout = self.stdout_capture;
+        let out_h = self.stdout_handler.clone();
+        let stdout_handle = match (output.merged, child.stdout.take()) {
+            (Some(merged), _) => Some(thread::spawn(move || {
+                read_lines(merged, out_h, capture_stdout)
+            })),
+            (None, Some(stdout)) => Some(thread::spawn(move || {
+                read_lines(stdout, out_h, capture_stdout)
+            })),
+            (None, None) => None,
+        };
+        let err_h = self.stderr_handler.clone();
+        let stderr_capture = self.stderr_capture;
+        let stderr_handle = child
+            .stderr
+            .take()
+            .map(|stderr| thread::spawn(move || read_lines(stderr, err_h, stderr_capture)));
+
+        Ok(XXChild {
+            command: self.to_string(),
+            child,
+            stdin,
+            stdin_handle,
+            stdout_handle,
+            stderr_handle,
+            watchdog: self.limits.watch(pid),
+            guard: self.group_guard(pid),
+            own_group: self.own_group(),
+            unchecked: self.unchecked,
+            grace_period: self.limits.grace_period,
+        })
+    }
+}
+
+impl XXChild {
+    /// The process ID, which is also the process group ID if it was started in its own
+    /// group
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The process's stdin, unless it was closed or given with
+    /// [`stdin_bytes`](XXExpression::stdin_bytes)
+    pub fn stdin(&mut self) -> Option<&mut ChildStdin> {
+        self.stdin.as_mut()
+    }
+
+    /// Close stdin so the process sees the end of its input
+    pub fn close_stdin(&mut self) {
+        self.stdin = None;
+    }
+
+    /// The exit status if the process has exited, without waiting
+    pub fn try_wait(&mut self) -> XXResult<Option<ExitStatus>> {
+        self.child
+            .try_wait()
+            .map_err(|err| XXError::ProcessError(err, self.command.clone()))
+    }
+
+    /// Wait for the process to exit and collect its output
+    ///
+    /// Stdin is closed first. Fails like [`XXExpression::run`] if the process exits
+    /// unsuccessfully, unless it is [`unchecked`](XXExpression::unchecked).
+    pub fn wait(mut self) -> XXResult<Output> {
+        self.close_stdin();
+        let status = self
+            .child
+            .wait()
+            .map_err(|err| XXError::ProcessError(err, self.command.clone()))?;
+        let (stdout, stderr) = self.finish(status)?;
+        if !self.unchecked && !status.success() {
+            return Err(XXError::ProcessFailed(Box::new(process_failure(
+                self.command.clone(),
+                status,
+                stdout.tail(),
+                stderr.tail(),
+            ))));
+        }
+        Ok(Output {
+            status,
+            stdout: stdout.captured,
+            stderr: stderr.captured,
+        })
+    }
+
+    /// Ask the process to exit with `SIGTERM`, kill it if it is still running after the
+    /// [`kill_grace_period`](XXExpression::kill_grace_period), and wait for it
+    ///
+    /// Unlike [`wait`](Self::wait), the exit status isn't checked, since being stopped
+    /// by a signal is expected here. On Windows, only a process started in its own group
+    /// is asked to exit first; others are killed right away.
+    pub fn shutdown(mut self) -> XXResult<ExitStatus> {
+        self.close_stdin();
+        if self.terminate()? {
+            let deadline = Instant::now() + self.grace_period;
+            while self.try_wait()?.is_none() && Instant::now() < deadline {
+                thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            }
+        }
+        if self.try_wait()?.is_none() {
+            self.kill()?;
+        }
+        let status = self
+            .child
+            .wait()
+            .map_err(|err| XXError::ProcessError(err, self.command.clone()))?;
+        // Stopping the process is the point, so don't treat it as a failure
+        if let Some(guard) = self.guard.take() {
+            guard.exited(true);
+        }
+        self.finish(status)?;
+        Ok(status)
+    }
+
+    /// Ask the process to exit, returning whether it was asked
+    fn terminate(&mut self) -> XXResult<bool> {
+        #[cfg(all(unix, feature = "process_unix"))]
+        self.signal(libc::SIGTERM)?;
+        #[cfg(all(unix, not(feature = "process_unix")))]
+        {
+            if self.own_group {
+                supervise::signal_group(self.pid(), false);
+            } else if self.try_wait()?.is_none() {
+                supervise::kill_command(&self.pid().to_string(), false);
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            if !self.own_group {
+                return Ok(false);
+            }
+            supervise::signal_group(self.pid(), false);
+        }
+        Ok(true)
+    }
+
+    /// Kill the process, or its whole group if it was started in its own, without
+    /// waiting for it to exit
+    pub fn kill(&mut self) -> XXResult<()> {
+        if self.own_group {
+            supervise::signal_group(self.pid(), true);
+            return Ok(());
+        }
+        match self.child.kill() {
+            // Already exited
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => Ok(()),
+            result => result.map_err(|err| XXError::ProcessError(err, self.command.clone())),
+        }
+    }
+
+    /// Send a signal to the process, or to its whole group if it was started in its
+    /// own (Unix only, requires the `process_unix` feature)
+    #[cfg(all(unix, feature = "process_unix"))]
+    pub fn signal(&mut self, signal: i32) -> XXResult<()> {
+        // Once reaped, the pid may belong to another process; a group's ID can't be
+        // reused while anything is left in it
+        if !self.own_group && self.try_wait()?.is_some() {
+            return Ok(());
+        }
+        let pid = self.pid() as libc::pid_t;
+        let target = if self.own_group { -pid } else { pid };
+        // SAFETY: kill has no memory safety requirements
+        match unsafe { libc::kill(target, signal) } {
+            0 => Ok(()),
+            _ => Err(XXError::ProcessError(
+                std::io::Error::last_os_error(),
+                self.command.clone(),
+            )),
+        }
+    }
+
+    /// Join the helper threads and check whether the process was stopped by a limit
+    fn finish(&mut self, status: ExitStatus) -> XXResult<(StreamedOutput, StreamedOutput)> {
+        if let Some(h) = self.stdin_handle.take() {
+            let _ = h.join();
+        }
+        let stdout = join_output(self.stdout_handle.take());
+        let stderr = join_output(self.stderr_handle.take());
+        if let Some(stop) = self.watchdog.take().and_then(Watchdog::finish) {
+            return Err(stop.into_error(self.command.clone()));
+        }
+        if let Some(guard) = self.guard.take() {
+            guard.exited(status.success());
+        }
+        Ok((stdout, stderr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::super::cmd;
+    use super::*;
+
+    #[test]
+    fn test_spawn_interactive() {
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let mut child = cmd("sh", ["-c", "while read line; do echo \"got $line\"; done"])
+            .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.into()))
+            .spawn()
+            .unwrap();
+        assert!(child.pid() > 0);
+        let stdin = child.stdin().unwrap();
+        stdin.write_all(b"one\n").unwrap();
+        stdin.flush().unwrap();
+        let start = Instant::now();
+        while lines.lock().unwrap().is_empty() && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(lines.lock().unwrap().as_slice(), ["got one"]);
+        assert!(child.try_wait().unwrap().is_none());
+
+        child.stdin().unwrap().write_all(b"two\n").unwrap();
+        let output = child.wait().unwrap();
+        assert!(output.status.success());
+        assert_eq!(lines.lock().unwrap().as_slice(), ["got one", "got two"]);
+    }
+
+    #[test]
+    fn test_spawn_failure() {
+        let child = cmd("sh", ["-c", "echo oops >&2; exit 3"])
+            .stderr_capture()
+            .spawn()
+            .unwrap();
+        let err = child.wait().unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.code, Some(3));
+        assert_eq!(failure.stderr, "oops");
+
+        let output = cmd("false", Vec::<&str>::new())
+            .unchecked()
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert!(!output.status.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_shutdown() {
+        // Exits cleanly on SIGTERM
+        let child = cmd(
+            "sh",
+            ["-c", "trap 'exit 0' TERM; while true; do sleep 0.05; done"],
+        )
+        .spawn()
+        .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let status = child.shutdown().unwrap();
+        assert!(status.success());
+
+        // Killed after the grace period when SIGTERM is ignored
+        let start = Instant::now();
+        let child = cmd(
+            "sh",
+            ["-c", "trap '' TERM; while true; do sleep 0.05; done"],
+        )
+        .kill_grace_period(Duration::from_millis(200))
+        .process_group()
+        .spawn()
+        .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let status = child.shutdown().unwrap();
+        assert!(!status.success());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(all(unix, feature = "process_unix"))]
+    #[test]
+    fn test_spawn_kill_and_signal() {
+        let mut child = cmd("sleep", ["10"]).spawn().unwrap();
+        child.signal(libc::SIGUSR1).unwrap();
+        let err = child.wait().unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.signal, Some(libc::SIGUSR1));
+
+        let mut child = cmd("sleep", ["10"]).unchecked().spawn().unwrap();
+        child.kill().unwrap();
+        let output = child.wait().unwrap();
+        assert_eq!(
+            std::os::unix::process::ExitStatusExt::signal(&output.status),
+            Some(libc::SIGKILL)
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Interleaved stdout and stderr
+//!
+//! [`XXExpression::run_combined`] reads stdout and stderr on separate pipes, like
+//! [`XXExpression::run`] with line handlers, and tags each line with the stream it
+//! came from and when it was read. Lines are delivered in the order they were read,
+//! which matches the order the command wrote them except for lines written to both
+//! streams at nearly the same moment.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::process::{self, Stream};
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! for line in process::cmd("cargo", ["build"]).run_combined()? {
+//!     let tag = match line.stream {
+//!         Stream::Stdout => "out",
+//!         Stream::Stderr => "err",
+//!     };
+//!     println!("[{tag}] {}", line.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::process::ExitStatus;
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
+use super::XXExpression;
+use crate::XXResult;
+
+/// Which output stream a line was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A line of output from [`XXExpression::run_combined`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    /// The stream the line was written to
+    pub stream: Stream,
+    /// The line, without its line ending
+    pub text: String,
+    /// When the line was read
+    pub at: SystemTime,
+}
+
+impl OutputLine {
+    pub(crate) fn new(stream: Stream, text: &str) -> Self {
+        Self {
+            stream,
+            text: text.to_string(),
+            at: SystemTime::now(),
+        }
+    }
+}
+
+impl XXExpression {
+    /// Run the command and return its stdout and stderr lines in the order they were
+    /// written
+    ///
+    /// Both streams are always piped, so output redirects and capture settings are
+    /// ignored; empty lines are skipped, as with [`on_stdout_line`](Self::on_stdout_line).
+    /// Fails like [`run`](Self::run) if the command exits unsuccessfully.
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process::{self, Stream};
+    /// let lines = process::cmd("sh", ["-c", "echo out; sleep 0.1; echo err >&2"])
+    ///     .run_combined()
+    ///     .unwrap();
+    /// assert_eq!(lines[0].stream, Stream::Stdout);
+    /// assert_eq!(lines[1].text, "err");
+    /// ```
+    pub fn run_combined(&self) -> XXResult<Vec<OutputLine>> {
+        let mut lines = vec![];
+        self.run_combined_with(|line| lines.push(line))?;
+        Ok(lines)
+    }
+
+    /// Like [`run_combined`](Self::run_combined), but pass each line to `handler` as
+    /// soon as it is read instead of collecting them
+    ///
+    /// The handler runs on the calling thread, one line at a time.
+    pub fn run_combined_with<F>(&self, mut handler: F) -> XXResult<ExitStatus>
+    where
+        F: FnMut(OutputLine),
+    {
+        debug!("$ {self}");
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            let run = scope.spawn(move || self.run_streaming(false, Some(tx)));
+            // Ends once the reader threads have finished and dropped their senders
+            for line in rx {
+                handler(line);
+            }
+            match run.join() {
+                Ok(output) => output.map(|output| output.status),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::super::cmd;
+    use super::*;
+    use crate::XXError;
+
+    #[test]
+    fn test_run_combined() {
+        let stdout_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let stdout_clone = stdout_lines.clone();
+        let script = "echo one; sleep 0.1; echo two >&2; sleep 0.1; echo three";
+        let lines = cmd("sh", ["-c", script])
+            .stdout_null()
+            .on_stdout_line(move |line| stdout_clone.lock().unwrap().push(line.into()))
+            .run_combined()
+            .unwrap();
+        let tagged: Vec<_> = lines
+            .iter()
+            .map(|line| (line.stream, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            tagged,
+            [
+                (Stream::Stdout, "one"),
+                (Stream::Stderr, "two"),
+                (Stream::Stdout, "three"),
+            ]
+        );
+        assert!(lines.windows(2).all(|w| w[0].at <= w[1].at));
+        // Line handlers still see their stream
+        assert_eq!(stdout_lines.lock().unwrap().as_slice(), ["one", "three"]);
+    }
+
+    #[test]
+    fn test_run_combined_with() {
+        let mut seen = vec![];
+        let status = cmd("sh", ["-c", "echo a; echo b >&2"])
+            .run_combined_with(|line| seen.push(line.stream))
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(seen.len(), 2);
+
+        let err = cmd("sh", ["-c", "echo bad >&2; exit 3"])
+            .run_combined_with(|line| seen.push(line.stream))
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.stderr, "bad");
+        assert_eq!(seen.last(), Some(&Stream::Stderr));
+
+        let status = cmd("false", Vec::<&str>::new())
+            .unchecked()
+            .run_combined_with(|_| {})
+            .unwrap();
+        assert!(!status.success());
+    }
+}
@@ -0,0 +1,227 @@
+//! Process groups and signal forwarding
+//!
+//! With [`XXExpression::process_group`](super::XXExpression::process_group), the child
+//! is started in its own process group and everything left in that group is killed if
+//! the command fails, or if the call running it returns early or is dropped (such as
+//! an async future that is no longer polled). A successful command's group is left
+//! alone.
+//!
+//! A child in its own group doesn't receive the `SIGINT` a terminal sends on Ctrl-C,
+//! because that goes to the terminal's foreground group. With
+//! [`XXExpression::forward_signals`](super::XXExpression::forward_signals), `SIGINT`,
+//! `SIGTERM` and `SIGHUP` received by this process are sent on to the group instead,
+//! the way a shell treats its foreground job. While such a command runs this process
+//! doesn't act on those signals itself; the command is expected to exit, and its
+//! failure is returned as usual. The previous signal handlers are restored once no
+//! forwarding command is running. Forwarding requires the `process_unix` feature.
+
+use super::supervise::signal_group;
+
+/// Kills a process group unless the command in it succeeded; see the module docs
+pub(crate) struct GroupGuard {
+    pid: u32,
+    armed: bool,
+    #[cfg(all(unix, feature = "process_unix"))]
+    forwarding: bool,
+}
+
+impl GroupGuard {
+    pub(crate) fn new(pid: u32, forward_signals: bool) -> Self {
+        #[cfg(all(unix, feature = "process_unix"))]
+        let forwarding = forward_signals && forward::register(pid);
+        #[cfg(not(all(unix, feature = "process_unix")))]
+        if forward_signals {
+            debug!(
+                "not forwarding signals to process {pid}: requires the process_unix feature on Unix"
+            );
+        }
+        Self {
+            pid,
+            armed: true,
+            #[cfg(all(unix, feature = "process_unix"))]
+            forwarding,
+        }
+    }
+
+    /// Record that the group leader exited, leaving the group alone if it succeeded
+    pub(crate) fn exited(mut self, success: bool) {
+        self.armed = !success;
+    }
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        #[cfg(all(unix, feature = "process_unix"))]
+        if self.forwarding {
+            forward::unregister(self.pid);
+        }
+        if self.armed {
+            debug!("killing process group {}", self.pid);
+            signal_group(self.pid, true);
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "process_unix"))]
+mod forward {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// Signals forwarded to running process groups
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// Most process groups that signals can be forwarded to at once
+    const MAX_GROUPS: usize = 64;
+
+    /// Groups to forward to, read by the signal handler; 0 is an empty slot
+    static GROUPS: [AtomicI32; MAX_GROUPS] = [const { AtomicI32::new(0) }; MAX_GROUPS];
+
+    /// Number of registered groups and the handlers to restore when it drops to 0
+    static INSTALLED: Mutex<(usize, Vec<(libc::c_int, libc::sigaction)>)> =
+        Mutex::new((0, Vec::new()));
+
+    extern "C" fn forward(signal: libc::c_int) {
+        for slot in &GROUPS {
+            let pid = slot.load(Ordering::SeqCst);
+            if pid != 0 {
+                // SAFETY: kill is async-signal-safe
+                unsafe {
+                    libc::kill(-pid, signal);
+                }
+            }
+        }
+    }
+
+    /// Start forwarding signals to the group led by `pid`, returning false if there
+    /// is no room for it
+    pub(super) fn register(pid: u32) -> bool {
+        let mut installed = INSTALLED.lock().unwrap();
+        let registered = GROUPS.iter().any(|slot| {
+            slot.compare_exchange(0, pid as i32, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        if !registered {
+            warn!("not forwarding signals to process {pid}: too many process groups");
+            return false;
+        }
+        if installed.0 == 0 {
+            trace!("installing signal forwarding handlers");
+            let handler = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            installed.1 = SIGNALS
+                .iter()
+                .map(|&signal| (signal, set_action(signal, handler)))
+                .collect();
+        }
+        installed.0 += 1;
+        true
+    }
+
+    /// Stop forwarding signals to the group led by `pid`
+    pub(super) fn unregister(pid: u32) {
+        let mut installed = INSTALLED.lock().unwrap();
+        if let Some(slot) = GROUPS
+            .iter()
+            .find(|slot| slot.load(Ordering::SeqCst) == pid as i32)
+        {
+            slot.store(0, Ordering::SeqCst);
+        }
+        installed.0 -= 1;
+        if installed.0 == 0 {
+            trace!("restoring signal handlers");
+            for (signal, previous) in installed.1.drain(..) {
+                // SAFETY: previous is the action returned by sigaction for this signal
+                unsafe {
+                    libc::sigaction(signal, &previous, std::ptr::null_mut());
+                }
+            }
+        }
+    }
+
+    /// Set the handler for `signal`, returning the previous action
+    fn set_action(signal: libc::c_int, handler: libc::sighandler_t) -> libc::sigaction {
+        // SAFETY: the handler only calls async-signal-safe functions, and zeroed
+        // sigaction structs are valid
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(signal, &action, &mut previous);
+            previous
+        }
+    }
+}
+
+#[cfg(all(test, unix, feature = "process_unix"))]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::super::cmd;
+    use crate::XXError;
+
+    fn is_running(pid: i32) -> bool {
+        // Reparented zombies may not be reaped right away in containers
+        std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .is_ok_and(|stat| !stat.contains(") Z "))
+    }
+
+    fn wait_until_stopped(pid: i32) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if !is_running(pid) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn test_process_group_killed_on_failure() {
+        let err = cmd("sh", ["-c", "sleep 30 >/dev/null & echo $!; exit 1"])
+            .process_group()
+            .read()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        let pid: i32 = failure.stdout.parse().unwrap();
+        assert!(
+            wait_until_stopped(pid),
+            "background process {pid} still running"
+        );
+
+        // Left alone when the command succeeds
+        let pid: i32 = cmd("sh", ["-c", "sleep 30 >/dev/null & echo $!"])
+            .process_group()
+            .read()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(is_running(pid));
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+
+    #[test]
+    fn test_forward_signals() {
+        let start = Instant::now();
+        // The handler is installed before output is read, so signalling this test
+        // process from the line handler doesn't interrupt it
+        let err = cmd("sh", ["-c", "echo ready; exec sleep 10"])
+            .forward_signals()
+            .on_stdout_line(|_| unsafe {
+                libc::kill(libc::getpid(), libc::SIGINT);
+            })
+            .run()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.signal, Some(libc::SIGINT));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,2124 @@
+//! Process execution utilities
+//!
+//! This module provides convenient functions and builders for executing external processes
+//! with better ergonomics than the standard library's `std::process` module.
+//!
+//! ## Features
+//!
+//! - Simple shell command execution with `sh()`, and `sh!` with safely quoted arguments
+//! - Builder pattern for complex command construction
+//! - Pipelines between commands without a shell
+//! - Automatic stdout/stderr capture options
+//! - Redirecting output to files or `/dev/null`, and merging stderr into stdout
+//! - Interleaved stdout and stderr lines tagged with their stream
+//! - Enhanced error messages that include the command that failed, shell-quoted and
+//!   with secrets redacted
+//! - Timeouts and cancellation that stop the whole process group
+//! - Process groups that are killed on failure, with Ctrl-C forwarded to them
+//! - Background children that can be written to, signalled and shut down
+//! - Running commands under a pseudo-terminal on Unix (`process_unix` feature)
+//! - Retrying failed commands with exponential backoff
+//!
+//! ## Examples
+//!
+//! ### Simple shell command
+//!
+//! ```rust,no_run
+//! use xx::process;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! // Run a shell command and get stdout as a string
+//! let output = process::sh("echo hello world")?;
+//! assert_eq!(output.trim(), "hello world");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ### Command builder
+//!
+//! ```rust,no_run
+//! use xx::process;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! // Build a command with arguments
+//! let output = process::cmd("git", &["status", "--short"])
+//!     .read()?;
+//!
+//! // Capture stdout and stderr separately
+//! let result = process::cmd("make", &["test"])
+//!     .stdout_capture()
+//!     .stderr_capture()
+//!     .run()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+use std::{ffi::OsString, fmt, io, process::Output};
+
+type LineHandler = dyn Fn(&str) + Send + Sync + 'static;
+
+use duct::IntoExecutablePath;
+
+use crate::error::ProcessFailure;
+use crate::{XXError, XXResult};
+
+pub use child::XXChild;
+pub use combined::{OutputLine, Stream};
+pub use pipeline::XXPipeline;
+pub use quote::{ShellStyle, redact, shell_quote};
+pub use retry::MAX_RETRY_DELAY;
+pub use shell::{Shell, sh_args};
+pub use supervise::{CancelHandle, DEFAULT_KILL_GRACE_PERIOD};
+
+mod child;
+mod combined;
+mod group;
+mod pipeline;
+mod pty;
+mod quote;
+mod retry;
+mod shell;
+mod supervise;
+
+pub fn sh(script: &str) -> XXResult<String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .stdin(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|err| XXError::ProcessError(err, sh_command(script)))?;
+
+    if !output.status.success() {
+        return Err(XXError::ProcessFailed(Box::new(process_failure(
+            sh_command(script),
+            output.status,
+            tail_lines(&output.stdout),
+            String::new(),
+        ))));
+    }
+    let stdout = String::from_utf8(output.stdout).expect("stdout is not utf-8");
+    Ok(stdout)
+}
+
+/// How [`sh`] displays its command in errors
+fn sh_command(script: &str) -> String {
+    format!("sh -c {}", shell_quote(&quote::redact_str(script, &[])))
+}
+
+pub fn check_status(status: ExitStatus) -> io::Result<()> {
+    if status.success() {
+        return Ok(());
+    }
+    let msg = if let Some(code) = status.code() {
+        format!("exited with code {code}")
+    } else {
+        "terminated by signal".to_string()
+    };
+    Err(io::Error::other(msg))
+}
+
+/// Number of lines of output kept in [`ProcessFailure`]
+const TAIL_LINES: usize = 20;
+
+fn process_failure(
+    command: String,
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+) -> ProcessFailure {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+    ProcessFailure {
+        command,
+        code: status.code(),
+        signal,
+        stdout,
+        stderr,
+        pipeline: None,
+    }
+}
+
+/// The last [`TAIL_LINES`] lines of some output
+fn tail_lines(output: &[u8]) -> String {
+    let output = String::from_utf8_lossy(output);
+    let lines: Vec<&str> = output.lines().collect();
+    lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n")
+}
+
+/// Lines read from a piped stdout or stderr
+#[derive(Default)]
+struct StreamedOutput {
+    /// Everything that was read, if capturing
+    captured: Vec<u8>,
+    /// The last lines, kept for error messages
+    tail: VecDeque<String>,
+}
+
+impl StreamedOutput {
+    fn tail(&self) -> String {
+        match self.tail.is_empty() {
+            true => tail_lines(&self.captured),
+            false => Vec::from(self.tail.clone()).join("\n"),
+        }
+    }
+}
+
+/// Wrap a line handler to also send each line to `combined`, tagged with `stream`
+fn tag_lines(
+    handler: Option<Arc<LineHandler>>,
+    combined: &Option<mpsc::Sender<OutputLine>>,
+    stream: Stream,
+) -> Option<Arc<LineHandler>> {
+    let Some(tx) = combined.clone() else {
+        return handler;
+    };
+    Some(Arc::new(move |line: &str| {
+        if let Some(h) = &handler {
+            (h)(line);
+        }
+        let _ = tx.send(OutputLine::new(stream, line));
+    }))
+}
+
+/// Wait for a thread reading output, if there is one
+fn join_output(handle: Option<thread::JoinHandle<StreamedOutput>>) -> StreamedOutput {
+    handle
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default()
+}
+
+/// Read a pipe to the end, keeping the raw bytes when capturing without a handler
+fn read_output<R: io::Read>(
+    mut pipe: R,
+    handler: Option<Arc<LineHandler>>,
+    capture: bool,
+) -> StreamedOutput {
+    if handler.is_none() && capture {
+        let mut output = StreamedOutput::default();
+        let _ = pipe.read_to_end(&mut output.captured);
+        return output;
+    }
+    read_lines(pipe, handler, capture)
+}
+
+/// How much forwarded output is kept for error messages
+const FORWARD_TAIL_BYTES: usize = 16 * 1024;
+
+/// Add `bytes` to the end of `recent`, keeping at most [`FORWARD_TAIL_BYTES`]
+fn keep_recent(recent: &mut VecDeque<u8>, bytes: &[u8]) {
+    recent.extend(bytes);
+    let excess = recent.len().saturating_sub(FORWARD_TAIL_BYTES);
+    recent.drain(..excess);
+}
+
+/// Copy a pipe to `out` unchanged, keeping the end of it for errors
+fn forward_output<R: io::Read, W: io::Write>(mut pipe: R, mut out: W) -> StreamedOutput {
+    let mut recent = VecDeque::with_capacity(FORWARD_TAIL_BYTES);
+    let mut buf = [0; 8192];
+    while let Ok(n @ 1..) = pipe.read(&mut buf) {
+        let _ = out.write_all(&buf[..n]);
+        let _ = out.flush();
+        keep_recent(&mut recent, &buf[..n]);
+    }
+    StreamedOutput {
+        captured: vec![],
+        tail: tail_lines(recent.make_contiguous())
+            .lines()
+            .map(String::from)
+            .collect(),
+    }
+}
+
+/// Read a pipe to the end, passing each non-empty line to the handler
+fn read_lines<R: io::Read>(
+    pipe: R,
+    handler: Option<Arc<LineHandler>>,
+    capture: bool,
+) -> StreamedOutput {
+    let mut output = StreamedOutput::default();
+    let mut reader = io::BufReader::new(pipe);
+    let mut line = String::with_capacity(1024);
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                let had_nl = line.ends_with('\n');
+                if had_nl {
+                    line.pop();
+                }
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+                if !line.is_empty() {
+                    if let Some(h) = &handler {
+                        (h)(&line);
+                    }
+                    if capture {
+                        output.captured.extend_from_slice(line.as_bytes());
+                    }
+                    if output.tail.len() == TAIL_LINES {
+                        output.tail.pop_front();
+                    }
+                    output.tail.push_back(line.clone());
+                }
+                if had_nl && capture {
+                    output.captured.push(b'\n');
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    output
+}
+
+/// Where stdout or stderr goes instead of being inherited or captured
+#[derive(Debug, Clone)]
+enum Redirect {
+    Null,
+    File {
+        path: PathBuf,
+        append: bool,
+    },
+    /// Only for stderr: wherever stdout goes
+    Stdout,
+}
+
+fn open_redirect(path: &Path, append: bool) -> XXResult<File> {
+    debug!("redirect: {path:?}");
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(|err| XXError::FileError(err, path.to_path_buf()))
+}
+
+/// Stdout and stderr for a `std::process::Command`
+struct OutputStdio {
+    stdout: Stdio,
+    stderr: Stdio,
+    /// Read end of a pipe that stdout and stderr both write to, for `stderr_to_stdout`
+    merged: Option<io::PipeReader>,
+}
+
+#[derive(Default)]
+pub struct XXExpression {
+    program: OsString,
+    args: Vec<OsString>,
+    stdout_capture: bool,
+    stderr_capture: bool,
+    stdout_redirect: Option<Redirect>,
+    stderr_redirect: Option<Redirect>,
+    stdout_handler: Option<Arc<LineHandler>>,
+    stderr_handler: Option<Arc<LineHandler>>,
+    env_vars: HashMap<OsString, OsString>,
+    env_clear: bool,
+    cwd: Option<PathBuf>,
+    stdin_data: Option<Vec<u8>>,
+    unchecked: bool,
+    limits: supervise::Limits,
+    process_group: bool,
+    forward_signals: bool,
+    redactions: Vec<String>,
+    pty: Option<pty::PtyConfig>,
+    retry: retry::Retry,
+}
+
+pub fn cmd<T, U>(program: T, args: U) -> XXExpression
+where
+    T: IntoExecutablePath,
+    U: IntoIterator,
+    U::Item: Into<OsString>,
+{
+    let program = program.to_executable();
+    let args = args.into_iter().map(|arg| arg.into()).collect::<Vec<_>>();
+    XXExpression {
+        program,
+        args,
+        ..Default::default()
+    }
+}
+
+impl XXExpression {
+    pub fn stdout_capture(mut self) -> Self {
+        self.stdout_capture = true;
+        self.stdout_redirect = None;
+        self
+    }
+
+    pub fn stderr_capture(mut self) -> Self {
+        self.stderr_capture = true;
+        self.stderr_redirect = None;
+        self
+    }
+
+    /// Write stdout to a file, truncating it
+    ///
+    /// Like the other output options, this replaces an earlier
+    /// [`stdout_capture`](Self::stdout_capture), [`stdout_append`](Self::stdout_append) or
+    /// [`stdout_null`](Self::stdout_null). Redirected output isn't passed to
+    /// [`on_stdout_line`](Self::on_stdout_line) handlers.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use xx::process;
+    /// process::cmd("cargo", ["build"])
+    ///     .stdout_path("build.log")
+    ///     .stderr_to_stdout()
+    ///     .run()
+    ///     .unwrap();
+    /// ```
+    pub fn stdout_path<P: AsRef<Path>>(self, path: P) -> Self {
+        self.redirect_stdout(Redirect::File {
+            path: path.as_ref().to_path_buf(),
+            append: false,
+        })
+    }
+
+    /// Append stdout to a file, creating it if needed
+    pub fn stdout_append<P: AsRef<Path>>(self, path: P) -> Self {
+        self.redirect_stdout(Redirect::File {
+            path: path.as_ref().to_path_buf(),
+            append: true,
+        })
+    }
+
+    /// Discard stdout
+    pub fn stdout_null(self) -> Self {
+        self.redirect_stdout(Redirect::Null)
+    }
+
+    /// Write stderr to a file, truncating it
+    ///
+    /// Replaces an earlier stderr option, see [`stdout_path`](Self::stdout_path).
+    pub fn stderr_path<P: AsRef<Path>>(self, path: P) -> Self {
+        self.redirect_stderr(Redirect::File {
+            path: path.as_ref().to_path_buf(),
+            append: false,
+        })
+    }
+
+    /// Append stderr to a file, creating it if needed
+    pub fn stderr_append<P: AsRef<Path>>(self, path: P) -> Self {
+        self.redirect_stderr(Redirect::File {
+            path: path.as_ref().to_path_buf(),
+            append: true,
+        })
+    }
+
+    /// Discard stderr
+    pub fn stderr_null(self) -> Self {
+        self.redirect_stderr(Redirect::Null)
+    }
+
+    /// Send stderr wherever stdout goes, like `2>&1`
+    ///
+    /// Merged output is captured by [`read`](Self::read) and
+    /// [`stdout_capture`](Self::stdout_capture) and passed to
+    /// [`on_stdout_line`](Self::on_stdout_line) handlers.
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let output = process::cmd("sh", ["-c", "echo out; echo err >&2"])
+    ///     .stderr_to_stdout()
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "out\nerr");
+    /// ```
+    pub fn stderr_to_stdout(self) -> Self {
+        self.redirect_stderr(Redirect::Stdout)
+    }
+
+    fn redirect_stdout(mut self, redirect: Redirect) -> Self {
+        self.stdout_capture = false;
+        self.stdout_redirect = Some(redirect);
+        self
+    }
+
+    fn redirect_stderr(mut self, redirect: Redirect) -> Self {
+        self.stderr_capture = false;
+        self.stderr_redirect = Some(redirect);
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<OsString>>) -> Self {
+        self.args.extend(args.into_iter().map(|arg| arg.into()));
+        self
+    }
+
+    pub fn run(&self) -> XXResult<Output> {
+        debug!("$ {self}");
+        self.with_retry(|| self.run_once())
+    }
+
+    fn run_once(&self) -> XXResult<Output> {
+        if let Some(pty) = &self.pty {
+            return self.run_pty(pty, self.stdout_capture);
+        }
+        if self.has_line_handlers() || self.forward_stderr() {
+            return self.run_streaming(self.stdout_capture, None);
+        }
+        self.run_expr(self.build_expr(self.stdout_capture)?)
+    }
+
+    pub fn read(&self) -> XXResult<String> {
+        debug!("$ {self}");
+        let output = self.with_retry(|| self.read_once())?;
+        self.stdout_string(output.stdout)
+    }
+
+    fn read_once(&self) -> XXResult<Output> {
+        if let Some(pty) = &self.pty {
+            self.run_pty(pty, true)
+        } else if self.has_line_handlers() || self.forward_stderr() {
+            self.run_streaming(true, None)
+        } else {
+            self.run_expr(self.build_expr(true)?)
+        }
+    }
+
+    /// Run the command under a pseudo-terminal (Unix only, requires the `process_unix`
+    /// feature)
+    ///
+    /// The command's stdin, stdout and stderr are a terminal, so tools that check
+    /// `isatty` print colors and progress. Its output is one stream, passed to the
+    /// [`on_stdout_line`](Self::on_stdout_line) handler and captured as stdout, or
+    /// written to this process's stdout if neither is set. Only [`run`](Self::run) and
+    /// [`read`](Self::read) use the terminal; it can't be combined with
+    /// [`stdin_bytes`](Self::stdin_bytes).
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(all(unix, feature = "process_unix"))] {
+    /// use xx::process;
+    /// let output = process::cmd("sh", ["-c", "test -t 1 && echo terminal"])
+    ///     .pty()
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "terminal");
+    /// # }
+    /// ```
+    pub fn pty(mut self) -> Self {
+        self.pty.get_or_insert_default();
+        self
+    }
+
+    /// Size of the pseudo-terminal in rows and columns (default: 24x80)
+    ///
+    /// Implies [`pty`](Self::pty).
+    pub fn pty_size(mut self, rows: u16, cols: u16) -> Self {
+        let pty = self.pty.get_or_insert_default();
+        pty.rows = rows;
+        pty.cols = cols;
+        self
+    }
+
+    /// Remove ANSI escape codes (colors, cursor movement, titles) from the
+    /// pseudo-terminal's output
+    ///
+    /// Implies [`pty`](Self::pty).
+    pub fn pty_strip_ansi(mut self) -> Self {
+        self.pty.get_or_insert_default().strip_ansi = true;
+        self
+    }
+
+    fn run_pty(&self, pty: &pty::PtyConfig, capture_stdout: bool) -> XXResult<Output> {
+        #[cfg(all(unix, feature = "process_unix"))]
+        return pty::run_pty(self, pty, capture_stdout);
+        #[cfg(not(all(unix, feature = "process_unix")))]
+        {
+            let _ = (pty, capture_stdout);
+            Err(crate::error!(
+                "pty requires Unix and the process_unix feature: {self}"
+            ))
+        }
+    }
+
+    fn has_line_handlers(&self) -> bool {
+        self.stdout_handler.is_some() || self.stderr_handler.is_some()
+    }
+
+    /// Whether stderr that would be inherited is piped and copied to this process's
+    /// stderr instead, so a retry predicate can see its end
+    fn forward_stderr(&self) -> bool {
+        self.retry.needs_stderr()
+            && !self.has_line_handlers()
+            && !self.stderr_capture
+            && self.stderr_redirect.is_none()
+    }
+
+    /// Run with stdout and stderr piped to the line handlers
+    ///
+    /// With `combined`, both streams are piped regardless of their settings and every
+    /// line is also sent there, tagged with its stream.
+    fn run_streaming(
+        &self,
+        capture_stdout: bool,
+        combined: Option<mpsc::Sender<OutputLine>>,
+    ) -> XXResult<Output> {
+        let mut cmd = self.command();
+        let output = match combined {
+            Some(_) => OutputStdio {
+                stdout: Stdio::piped(),
+                stderr: Stdio::piped(),
+                merged: None,
+            },
+            None => self.output_stdio(capture_stdout)?,
+        };
+        cmd.stdout(output.stdout).stderr(output.stderr);
+
+        // Handle stdin
+        if self.stdin_data.is_some() {
+            cmd.stdin(Stdio::piped());
+        } else {
+            cmd.stdin(Stdio::inherit());
+        }
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        // Close our copies of the merged pipe's write end so reading it can finish
+        drop(cmd);
+        let watchdog = self.limits.watch(child.id());
+        let guard = self.group_guard(child.id());
+
+        // Write stdin data in a separate thread to avoid deadlock when combining
+        // large stdin with stdout/stderr handlers. Without this, if stdin data
+        // exceeds the pipe buffer (~64KB) and the child fills its stdout buffer
+        // before consuming stdin, both parent and child would block.
+        let stdin_handle = self.stdin_data.clone().and_then(|stdin_data| {
+            child.stdin.take().map(|mut stdin| {
+                thread::spawn(move || {
+                    use std::io::Write;
+                    let _ = stdin.write_all(&stdin_data);
+                })
+            })
+        });
+
+        // Output that isn't piped (inherited or redirected) has nothing to read
+        let out_h = tag_lines(self.stdout_handler.clone(), &combined, Stream::Stdout);
+        let stdout_handle = match (output.merged, child.stdout.take()) {
+            (Some(merged), _) => Some(thread::spawn(move || {
+                read_lines(merged, out_h, capture_stdout)
+            })),
+            (None, Some(stdout)) => Some(thread::spawn(move || {
+                read_lines(stdout, out_h, capture_stdout)
+            })),
+            (None, None) => None,
+        };
+        let forward_stderr = combined.is_none() && self.forward_stderr();
+        let err_h = tag_lines(self.stderr_handler.clone(), &combined, Stream::Stderr);
+        // The reader threads hold the only senders, so the receiver ends with them
+        drop(combined);
+        let stderr_capture = self.stderr_capture;
+        let stderr_handle = child.stderr.take().map(|stderr| {
+            thread::spawn(move || match forward_stderr {
+                true => forward_output(stderr, io::stderr()),
+                false => read_lines(stderr, err_h, stderr_capture),
+            })
+        });
+
+        let status = child
+            .wait()
+            .map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+
+        if let Some(h) = stdin_handle {
+            let _ = h.join();
+        }
+        let stdout = join_output(stdout_handle);
+        let stderr = join_output(stderr_handle);
+        self.check_stopped(watchdog)?;
+        if let Some(guard) = guard {
+            guard.exited(status.success());
+        }
+        self.check_exit(status, || (stdout.tail(), stderr.tail()))?;
+        Ok(Output {
+            status,
+            stdout: stdout.captured,
+            stderr: stderr.captured,
+        })
+    }
+
+    /// A `std::process::Command` with this expression's program, arguments, environment
+    /// and working directory
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+
+        // Handle environment
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        for (k, v) in &self.env_vars {
+            cmd.env(k, v);
+        }
+
+        // Handle working directory
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        self.set_process_group(&mut cmd);
+        cmd
+    }
+
+    /// Stdout and stderr for this expression when run without duct
+    ///
+    /// Stdout is piped when `capture_stdout` is set or it has a line handler and no
+    /// redirect; stderr likewise with [`stderr_capture`](Self::stderr_capture).
+    fn output_stdio(&self, capture_stdout: bool) -> XXResult<OutputStdio> {
+        let stdout_redirect = self.stdout_redirect.as_ref().filter(|_| !capture_stdout);
+        // A line handler on either stream pipes both, discarding output that is neither
+        // handled nor captured, unless it's redirected
+        let handled = self.stdout_handler.is_some() || self.stderr_handler.is_some();
+        let pipe_stdout = capture_stdout || (stdout_redirect.is_none() && handled);
+        let stdout_file = match stdout_redirect {
+            Some(Redirect::File { path, append }) => Some(open_redirect(path, *append)?),
+            _ => None,
+        };
+        let try_clone = |file: &File| {
+            file.try_clone()
+                .map_err(|err| XXError::ProcessError(err, self.to_string()))
+        };
+
+        let mut merged = None;
+        let stderr = match &self.stderr_redirect {
+            Some(Redirect::Null) => Stdio::null(),
+            Some(Redirect::File { path, append }) => open_redirect(path, *append)?.into(),
+            Some(Redirect::Stdout) => match (&stdout_file, stdout_redirect) {
+                (Some(file), _) => try_clone(file)?.into(),
+                (None, Some(_)) => Stdio::null(),
+                (None, None) if pipe_stdout => {
+                    let (reader, writer) =
+                        io::pipe().map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+                    let stderr = writer
+                        .try_clone()
+                        .map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+                    merged = Some((reader, writer));
+                    stderr.into()
+                }
+                (None, None) => io::stdout().into(),
+            },
+            None if self.stderr_capture || handled || self.forward_stderr() => Stdio::piped(),
+            None => Stdio::inherit(),
+        };
+        let (stdout, merged) = match merged {
+            Some((reader, writer)) => (writer.into(), Some(reader)),
+            None => {
+                let stdout = match (stdout_file, stdout_redirect) {
+                    (Some(file), _) => file.into(),
+                    (None, Some(_)) => Stdio::null(),
+                    (None, None) if pipe_stdout => Stdio::piped(),
+                    (None, None) => Stdio::inherit(),
+                };
+                (stdout, None)
+            }
+        };
+        Ok(OutputStdio {
+            stdout,
+            stderr,
+            merged,
+        })
+    }
+
+    /// Fail with [`XXError::ProcessFailed`] if the process exited unsuccessfully
+    ///
+    /// `tails` returns the end of stdout and stderr and is only called on failure.
+    fn check_exit<F>(&self, status: ExitStatus, tails: F) -> XXResult<()>
+    where
+        F: FnOnce() -> (String, String),
+    {
+        if self.unchecked || status.success() {
+            return Ok(());
+        }
+        let (stdout, stderr) = tails();
+        Err(XXError::ProcessFailed(Box::new(process_failure(
+            self.to_string(),
+            status,
+            stdout,
+            stderr,
+        ))))
+    }
+
+    /// Convert captured stdout to a string, trimming trailing newlines like `duct::Expression::read`
+    fn stdout_string(&self, stdout: Vec<u8>) -> XXResult<String> {
+        let mut stdout = String::from_utf8(stdout).map_err(|_| {
+            XXError::ProcessError(
+                io::Error::new(io::ErrorKind::InvalidData, "stdout is not utf-8"),
+                self.to_string(),
+            )
+        })?;
+        while stdout.ends_with('\n') || stdout.ends_with('\r') {
+            stdout.pop();
+        }
+        Ok(stdout)
+    }
+
+    /// Register a line-by-line stdout handler. When set, `run()` will stream output lines
+    /// to this handler instead of capturing stdout, unless [`stdout_capture`](Self::stdout_capture)
+    /// is also set.
+    ///
+    /// Stderr is then piped too: without [`on_stderr_line`](Self::on_stderr_line),
+    /// [`stderr_capture`](Self::stderr_capture) or a redirect, it is discarded.
+    pub fn on_stdout_line<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.stdout_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Register a line-by-line stderr handler. When set, `run()` will stream error lines
+    /// to this handler instead of capturing stderr, unless [`stderr_capture`](Self::stderr_capture)
+    /// is also set.
+    ///
+    /// Stdout is then piped too: without [`on_stdout_line`](Self::on_stdout_line),
+    /// capture or a redirect, it is discarded.
+    pub fn on_stderr_line<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.stderr_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Set an environment variable for this command
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let output = process::cmd("sh", ["-c", "echo $MY_VAR"])
+    ///     .env("MY_VAR", "hello")
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "hello");
+    /// ```
+    pub fn env<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        self.env_vars.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set multiple environment variables for this command
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// use std::collections::HashMap;
+    /// let mut env = HashMap::new();
+    /// env.insert("VAR1", "value1");
+    /// env.insert("VAR2", "value2");
+    /// let output = process::cmd("sh", ["-c", "echo $VAR1 $VAR2"])
+    ///     .envs(env)
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "value1 value2");
+    /// ```
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        for (k, v) in vars {
+            self.env_vars.insert(k.into(), v.into());
+        }
+        self
+    }
+
+    /// Clear all environment variables before running (start with empty environment)
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let output = process::cmd("sh", ["-c", "echo ${PATH:-empty}"])
+    ///     .env_clear()
+    ///     .env("PATH", "/bin:/usr/bin")
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "/bin:/usr/bin");
+    /// ```
+    pub fn env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self
+    }
+
+    /// Set the working directory for this command
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let output = process::cmd("pwd", Vec::<&str>::new())
+    ///     .cwd("/tmp")
+    ///     .read()
+    ///     .unwrap();
+    /// assert!(output.contains("tmp"));
+    /// ```
+    pub fn cwd<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cwd = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Provide stdin data as bytes
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let output = process::cmd("cat", Vec::<&str>::new())
+    ///     .stdin_bytes(b"hello world")
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "hello world");
+    /// ```
+    pub fn stdin_bytes<B: AsRef<[u8]>>(mut self, data: B) -> Self {
+        self.stdin_data = Some(data.as_ref().to_vec());
+        self
+    }
+
+    /// Provide stdin data from a file
+    /// # Example
+    /// ```no_run
+    /// use xx::process;
+    /// let output = process::cmd("cat", Vec::<&str>::new())
+    ///     .stdin_file("input.txt")
+    ///     .unwrap()
+    ///     .read()
+    ///     .unwrap();
+    /// ```
+    pub fn stdin_file<P: AsRef<Path>>(mut self, path: P) -> XXResult<Self> {
+        let path = path.as_ref();
+        let data =
+            std::fs::read(path).map_err(|err| XXError::FileError(err, path.to_path_buf()))?;
+        self.stdin_data = Some(data);
+        Ok(self)
+    }
+
+    /// Don't check the exit status (allow non-zero exit codes)
+    ///
+    /// By default, `run()` and `read()` return an error if the process exits
+    /// with a non-zero status. This method disables that check.
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// // This would normally error because false exits with code 1
+    /// let output = process::cmd("false", Vec::<&str>::new())
+    ///     .unchecked()
+    ///     .run()
+    ///     .unwrap();
+    /// assert!(!output.status.success());
+    /// ```
+    pub fn unchecked(mut self) -> Self {
+        self.unchecked = true;
+        self
+    }
+
+    /// Mask `secret` wherever it appears when this command is displayed in logs and
+    /// errors
+    ///
+    /// Environment variables with names like `*_TOKEN` or `*_PASSWORD` are always
+    /// masked; see [`process::redact`](redact) to mask a value in every command.
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let expr = process::cmd("login", ["--password", "hunter2"]).redact("hunter2");
+    /// assert_eq!(expr.to_string(), "login --password '[REDACTED]'");
+    /// ```
+    pub fn redact(mut self, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        if !secret.is_empty() {
+            self.redactions.push(secret);
+        }
+        self
+    }
+
+    /// The command line quoted for `style`, including environment overrides and the
+    /// working directory, with secrets masked
+    ///
+    /// [`Display`](fmt::Display) uses [`ShellStyle::native`].
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process::{self, ShellStyle};
+    /// let expr = process::cmd("echo", ["hello world"]).env("LANG", "C").cwd("/tmp");
+    /// assert_eq!(
+    ///     expr.to_shell_string(ShellStyle::Posix),
+    ///     "cd /tmp && LANG=C echo 'hello world'"
+    /// );
+    /// assert_eq!(
+    ///     expr.to_shell_string(ShellStyle::PowerShell),
+    ///     "Set-Location -LiteralPath /tmp; $env:LANG = 'C'; echo 'hello world'"
+    /// );
+    /// ```
+    pub fn to_shell_string(&self, style: ShellStyle) -> String {
+        self.shell_string(style, false)
+    }
+
+    /// See [`to_shell_string`](Self::to_shell_string); `grouped` wraps commands with a
+    /// working directory or environment so they can be part of a pipeline
+    fn shell_string(&self, style: ShellStyle, grouped: bool) -> String {
+        let quote = |s: &std::ffi::OsStr| {
+            style.quote(&quote::redact_str(&s.to_string_lossy(), &self.redactions))
+        };
+        let mut env: Vec<_> = self.env_vars.iter().collect();
+        env.sort();
+        let env = env.into_iter().map(|(k, v)| {
+            let k = k.to_string_lossy();
+            let v = v.to_string_lossy();
+            let v = quote::env_value(&k, &v, &self.redactions);
+            let v = match style {
+                // Assignments are parsed as expressions, where bare words are commands
+                ShellStyle::PowerShell => style.quote_always(&v),
+                ShellStyle::Posix => style.quote(&v),
+            };
+            (k, v)
+        });
+        let args = self.args.iter().map(|arg| quote(arg));
+        let cwd = self.cwd.as_ref().map(|cwd| quote(cwd.as_os_str()));
+        match style {
+            ShellStyle::Posix => {
+                let mut words = vec![];
+                if self.env_clear {
+                    words.push("env -i".to_string());
+                }
+                words.extend(env.map(|(k, v)| format!("{k}={v}")));
+                words.push(quote(&self.program));
+                words.extend(args);
+                let command = words.join(" ");
+                match cwd {
+                    Some(cwd) if grouped => format!("(cd {cwd} && {command})"),
+                    Some(cwd) => format!("cd {cwd} && {command}"),
+                    None => command,
+                }
+            }
+            ShellStyle::PowerShell => {
+                let mut statements: Vec<String> = cwd
+                    .map(|cwd| format!("Set-Location -LiteralPath {cwd}"))
+                    .into_iter()
+                    .collect();
+                statements.extend(env.map(|(k, v)| format!("$env:{k} = {v}")));
+                let program = self.program.to_string_lossy();
+                let mut words = vec![match quote(&self.program) {
+                    quoted if quoted != program => format!("& {quoted}"),
+                    quoted => quoted,
+                }];
+                words.extend(args);
+                statements.push(words.join(" "));
+                match statements.len() {
+                    1 => statements.swap_remove(0),
+                    _ if grouped => format!("& {{ {} }}", statements.join("; ")),
+                    _ => statements.join("; "),
+                }
+            }
+        }
+    }
+
+    /// Stop the process if it runs longer than `timeout`
+    ///
+    /// The process and everything it started are sent `SIGTERM`, then `SIGKILL` after
+    /// the [grace period](Self::kill_grace_period), and the command fails with
+    /// [`XXError::ProcessTimeout`]. This applies even with [`unchecked`](Self::unchecked).
+    ///
+    /// To stop everything the process started, it runs in its own process group on
+    /// Unix. That group isn't the terminal's foreground group, so Ctrl-C in the
+    /// terminal doesn't reach it unless [`forward_signals`](Self::forward_signals) is
+    /// set, and a command that reads from the terminal, such as a password prompt, is
+    /// stopped by `SIGTTIN` until the timeout. Give such commands their input through
+    /// [`stdin_bytes`](Self::stdin_bytes) or the environment instead.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use xx::process;
+    /// let result = process::cmd("sleep", ["10"])
+    ///     .timeout(Duration::from_millis(100))
+    ///     .run();
+    /// assert!(matches!(result, Err(xx::XXError::ProcessTimeout(..))));
+    /// ```
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Stop the process when `handle` is cancelled, failing with
+    /// [`XXError::ProcessCancelled`]
+    ///
+    /// Like with [`timeout`](Self::timeout), the process runs in its own process group,
+    /// so it can't read from the terminal and only gets Ctrl-C with
+    /// [`forward_signals`](Self::forward_signals). See [`CancelHandle`] for an example.
+    pub fn cancel_handle(mut self, handle: &CancelHandle) -> Self {
+        self.limits.cancel = Some(handle.clone());
+        self
+    }
+
+    /// How long to wait after `SIGTERM` before killing a timed out or cancelled process
+    /// (default: 5 seconds)
+    pub fn kill_grace_period(mut self, grace_period: Duration) -> Self {
+        self.limits.grace_period = grace_period;
+        self
+    }
+
+    /// Start the command in its own process group, and kill everything in the group if
+    /// the command fails or running it is abandoned
+    ///
+    /// This stops background processes the command leaves behind when it fails, and
+    /// processes started by an async call whose future is dropped. See
+    /// [`forward_signals`](Self::forward_signals) to also pass on Ctrl-C.
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// // The background sleep is killed when the script fails
+    /// let result = process::cmd("sh", ["-c", "sleep 60 & exit 1"])
+    ///     .process_group()
+    ///     .run();
+    /// assert!(result.is_err());
+    /// ```
+    pub fn process_group(mut self) -> Self {
+        self.process_group = true;
+        self
+    }
+
+    /// Forward `SIGINT`, `SIGTERM` and `SIGHUP` received by this process to the command's
+    /// process group while it runs (Unix only, requires the `process_unix` feature)
+    ///
+    /// Implies [`process_group`](Self::process_group). While the command runs, this
+    /// process doesn't act on those signals itself: the command is expected to exit,
+    /// and its failure is returned as usual, so a CLI can clean up before exiting. The
+    /// previous signal handlers are restored afterwards.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use xx::process;
+    /// // Ctrl-C stops the build, then `run` returns an error
+    /// process::cmd("cargo", ["build"]).forward_signals().run().unwrap();
+    /// ```
+    pub fn forward_signals(mut self) -> Self {
+        self.process_group = true;
+        self.forward_signals = true;
+        self
+    }
+
+    /// Whether the command is started in its own process group
+    fn own_group(&self) -> bool {
+        self.process_group || self.limits.is_active()
+    }
+
+    /// Guard that kills the command's process group unless it succeeds
+    fn group_guard(&self, pid: u32) -> Option<group::GroupGuard> {
+        self.process_group
+            .then(|| group::GroupGuard::new(pid, self.forward_signals))
+    }
+
+    /// Start the process in its own process group so it can be stopped along with its
+    /// children, when a timeout, cancel handle or process group is set
+    fn set_process_group(&self, cmd: &mut Command) {
+        // A pseudo-terminal's session is already a process group
+        #[cfg(unix)]
+        if self.own_group() && self.pty.is_none() {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+        #[cfg(not(unix))]
+        let _ = cmd;
+    }
+
+    fn check_stopped(&self, watchdog: Option<supervise::Watchdog>) -> XXResult<()> {
+        match watchdog.and_then(supervise::Watchdog::finish) {
+            Some(stop) => Err(stop.into_error(self.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn run_expr(&self, expr: duct::Expression) -> XXResult<Output> {
+        // The exit status is checked here rather than by duct so that failures include
+        // the captured output
+        let handle = expr
+            .unchecked()
+            .start()
+            .map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        let pid = handle.pids().first().copied();
+        let watchdog = pid.and_then(|pid| self.limits.watch(pid));
+        let guard = pid.and_then(|pid| self.group_guard(pid));
+        let output = handle.wait().cloned();
+        self.check_stopped(watchdog)?;
+        let output = output.map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        if let Some(guard) = guard {
+            guard.exited(output.status.success());
+        }
+        self.check_exit(output.status, || {
+            (tail_lines(&output.stdout), tail_lines(&output.stderr))
+        })?;
+        Ok(output)
+    }
+
+    fn build_expr(&self, capture_stdout: bool) -> XXResult<duct::Expression> {
+        let mut expr = duct::cmd(self.program.clone(), self.args.clone());
+        // duct applies outer redirects first, so stderr_to_stdout must be inside the
+        // stdout redirect to follow it
+        if self.stderr_capture {
+            expr = expr.stderr_capture();
+        }
+        expr = match &self.stderr_redirect {
+            Some(Redirect::Null) => expr.stderr_null(),
+            Some(Redirect::File { path, append }) => {
+                expr.stderr_file(open_redirect(path, *append)?)
+            }
+            Some(Redirect::Stdout) => expr.stderr_to_stdout(),
+            None => expr,
+        };
+        expr = match &self.stdout_redirect {
+            _ if capture_stdout => expr.stdout_capture(),
+            Some(Redirect::Null) => expr.stdout_null(),
+            Some(Redirect::File { path, append }) => {
+                expr.stdout_file(open_redirect(path, *append)?)
+            }
+            Some(Redirect::Stdout) | None => expr,
+        };
+        if self.env_clear {
+            expr = expr.full_env(self.env_vars.clone());
+        } else {
+            for (k, v) in &self.env_vars {
+                expr = expr.env(k, v);
+            }
+        }
+        if let Some(cwd) = &self.cwd {
+            expr = expr.dir(cwd);
+        }
+        if let Some(stdin_data) = &self.stdin_data {
+            expr = expr.stdin_bytes(stdin_data.clone());
+        }
+        if self.own_group() {
+            expr = expr.before_spawn(|cmd| {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::process::CommandExt;
+                    cmd.process_group(0);
+                }
+                #[cfg(not(unix))]
+                let _ = cmd;
+                Ok(())
+            });
+        }
+        Ok(expr)
+    }
+}
+
+#[cfg(feature = "process_async")]
+impl XXExpression {
+    /// Run the command on the tokio runtime
+    ///
+    /// The async counterpart of [`run`](Self::run), built on `tokio::process`. It uses
+    /// the same options (env, cwd, stdin, capture, line handlers, unchecked), so it can
+    /// run alongside other async work such as downloads. Line handlers are called in
+    /// order on tokio's blocking thread pool as lines arrive, so they can block (for
+    /// example on a mutex or a file write) without stalling the runtime. Requires the
+    /// `process_async` feature.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use xx::process;
+    ///
+    /// # async fn example() -> xx::XXResult<()> {
+    /// let build = process::cmd("cargo", ["build"]);
+    /// let test = process::cmd("cargo", ["test"]).stdout_capture();
+    /// let (build, test) = tokio::join!(build.run_async(), test.run_async());
+    /// assert!(build?.status.success());
+    /// println!("{}", String::from_utf8_lossy(&test?.stdout));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_async(&self) -> XXResult<Output> {
+        debug!("$ {self}");
+        let output = self
+            .with_retry_async(|| self.output_async(self.stdout_capture))
+            .await?;
+        if self.has_line_handlers() || self.forward_stderr() {
+            // Like `run()`, streamed output goes to the handlers unless also captured
+            let keep = |output: Vec<u8>, capture: bool| if capture { output } else { vec![] };
+            return Ok(Output {
+                status: output.status,
+                stdout: keep(output.stdout, self.stdout_capture),
+                stderr: keep(output.stderr, self.stderr_capture),
+            });
+        }
+        Ok(output)
+    }
+
+    /// Run the command on the tokio runtime and return its stdout
+    ///
+    /// The async counterpart of [`read`](Self::read): trailing newlines are trimmed and
+    /// stderr is inherited unless captured or handled. Requires the `process_async`
+    /// feature.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use xx::process;
+    ///
+    /// # async fn example() -> xx::XXResult<()> {
+    /// let sha = process::cmd("git", ["rev-parse", "HEAD"]).read_async().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_async(&self) -> XXResult<String> {
+        debug!("$ {self}");
+        let output = self.with_retry_async(|| self.output_async(true)).await?;
+        self.stdout_string(output.stdout)
+    }
+
+    async fn output_async(&self, capture_stdout: bool) -> XXResult<Output> {
+        use tokio::io::AsyncWriteExt;
+
+        let output = self.output_stdio(capture_stdout)?;
+        let mut cmd = tokio::process::Command::new(&self.program);
+        cmd.args(&self.args)
+            .stdin(match self.stdin_data.is_some() {
+                true => Stdio::piped(),
+                false => Stdio::inherit(),
+            })
+            .stdout(output.stdout)
+            .stderr(output.stderr);
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        cmd.envs(&self.env_vars);
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        #[cfg(unix)]
+        if self.own_group() {
+            cmd.process_group(0);
+        }
+        // Dropping the future stops the process
+        cmd.kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        // Close our copies of the merged pipe's write end so reading it can finish
+        drop(cmd);
+        let pid = child.id();
+        // Dropped along with the future, killing the group if it's still running
+        let guard = pid.and_then(|pid| self.group_guard(pid));
+
+        // Write stdin while reading stdout/stderr so neither side blocks on a full pipe
+        let stdin = child.stdin.take();
+        let write_stdin = async {
+            if let (Some(mut stdin), Some(data)) = (stdin, &self.stdin_data) {
+                let _ = stdin.write_all(data).await;
+            }
+        };
+        let child_stdout = child.stdout.take();
+        let stdout_handler = self.stdout_handler.clone();
+        let stdout = async move {
+            match output.merged {
+                // std pipes have no async reader, so read on the blocking pool
+                Some(merged) => tokio::task::spawn_blocking(move || {
+                    read_lines(merged, stdout_handler, capture_stdout).captured
+                })
+                .await
+                .map_err(io::Error::other),
+                None => read_pipe_async(child_stdout, stdout_handler).await,
+            }
+        };
+        let child_stderr = child.stderr.take();
+        let stderr = async {
+            match self.forward_stderr() {
+                true => forward_pipe_async(child_stderr).await,
+                false => read_pipe_async(child_stderr, self.stderr_handler.clone()).await,
+            }
+        };
+        let io = async {
+            let ((), stdout, stderr) = tokio::join!(write_stdin, stdout, stderr);
+            (stdout, stderr, child.wait().await)
+        };
+        tokio::pin!(io);
+
+        let (stdout, stderr, status) = match pid.filter(|_| self.limits.is_active()) {
+            Some(pid) => tokio::select! {
+                out = &mut io => out,
+                stop = self.limits.exceeded() => {
+                    debug!("stopping process {pid}: {stop:?}");
+                    supervise::signal_group(pid, false);
+                    let exited = tokio::time::timeout(self.limits.grace_period, &mut io)
+                        .await
+                        .is_ok();
+                    supervise::signal_group(pid, true);
+                    if !exited {
+                        let _ = io.await;
+                    }
+                    return Err(stop.into_error(self.to_string()));
+                }
+            },
+            None => io.await,
+        };
+        let status = status.map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        if let Some(guard) = guard {
+            guard.exited(status.success());
+        }
+        let stdout = stdout.map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        let stderr = stderr.map_err(|err| XXError::ProcessError(err, self.to_string()))?;
+        self.check_exit(status, || (tail_lines(&stdout), tail_lines(&stderr)))?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+/// Read a child's output pipe to the end, passing each non-empty line to the handler
+///
+/// The handler is called in order on the blocking thread pool, so a slow handler
+/// doesn't stall the runtime. With a handler, the returned output has `\r\n` line
+/// endings normalized to `\n`.
+#[cfg(feature = "process_async")]
+async fn read_pipe_async<R>(
+    pipe: Option<R>,
+    handler: Option<Arc<LineHandler>>,
+) -> io::Result<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use std::sync::mpsc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    let Some(mut pipe) = pipe else {
+        return Ok(vec![]);
+    };
+    let mut acc = vec![];
+    let Some(handler) = handler else {
+        pipe.read_to_end(&mut acc).await?;
+        return Ok(acc);
+    };
+    let (tx, rx) = mpsc::channel::<String>();
+    let calls = tokio::task::spawn_blocking(move || {
+        for line in rx {
+            (handler)(&line);
+        }
+    });
+    let mut reader = tokio::io::BufReader::new(pipe);
+    let mut line = Vec::with_capacity(1024);
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        let had_nl = line.ends_with(b"\n");
+        while line.ends_with(b"\n") || line.ends_with(b"\r") {
+            line.pop();
+        }
+        if !line.is_empty() {
+            let _ = tx.send(String::from_utf8_lossy(&line).into_owned());
+            acc.extend_from_slice(&line);
+        }
+        if had_nl {
+            acc.push(b'\n');
+        }
+    }
+    // Wait for the handler to see every line before the output is returned
+    drop(tx);
+    calls.await.map_err(io::Error::other)?;
+    Ok(acc)
+}
+
+/// Copy a child's output pipe to this process's stderr unchanged, returning the end
+/// of it for errors
+#[cfg(feature = "process_async")]
+async fn forward_pipe_async<R>(pipe: Option<R>) -> io::Result<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let Some(mut pipe) = pipe else {
+        return Ok(vec![]);
+    };
+    let mut out = tokio::io::stderr();
+    let mut recent = VecDeque::with_capacity(FORWARD_TAIL_BYTES);
+    let mut buf = [0; 8192];
+    loop {
+        let n = pipe.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        let _ = out.write_all(&buf[..n]).await;
+        let _ = out.flush().await;
+        keep_recent(&mut recent, &buf[..n]);
+    }
+    Ok(recent.into())
+}
+
+impl fmt::Display for XXExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_shell_string(ShellStyle::native()))
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_cmd() {
+        let expr = cmd("echo", ["hello", "world"]).stdout_capture();
+        let output = expr.run().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello world\n");
+    }
+
+    #[test]
+    fn test_cmd_read() {
+        let expr = cmd("echo", ["hello"]).arg("world").args(["foo", "bar"]);
+        let output = expr.read().unwrap();
+        assert_eq!(output, "hello world foo bar");
+    }
+
+    #[test]
+    fn test_line_handlers_capture_stdout_and_stderr_lines() {
+        // Use sh to emit interleaved stdout/stderr lines
+        let script = r#"
+            printf 'o1\n';
+            printf 'e1\n' 1>&2;
+            printf 'o2\n';
+            printf 'e2\n' 1>&2;
+        "#;
+        let out_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let err_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+
+        let out_clone = out_lines.clone();
+        let err_clone = err_lines.clone();
+
+        let output = cmd("sh", ["-c", script])
+            .on_stdout_line(move |line| out_clone.lock().unwrap().push(line.to_string()))
+            .on_stderr_line(move |line| err_clone.lock().unwrap().push(line.to_string()))
+            .run()
+            .unwrap();
+        assert!(output.status.success());
+
+        let mut out = out_lines.lock().unwrap().clone();
+        let mut err = err_lines.lock().unwrap().clone();
+        out.sort();
+        err.sort();
+        assert_eq!(out, vec!["o1", "o2"]);
+        assert_eq!(err, vec!["e1", "e2"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_line_handler_pipes_other_stream() {
+        // A handler on one stream keeps the other one off this process's output
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        cmd("sh", ["-c", "readlink /proc/$$/fd/2"])
+            .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.to_string()))
+            .run()
+            .unwrap();
+        let ours = std::fs::read_link("/proc/self/fd/2").unwrap();
+        let theirs = lines.lock().unwrap()[0].clone();
+        assert!(theirs.starts_with("pipe:"), "{theirs}");
+        assert_ne!(PathBuf::from(theirs), ours);
+
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let output = cmd("sh", ["-c", "readlink /proc/$$/fd/1 >&2"])
+            .on_stderr_line(move |line| lines_clone.lock().unwrap().push(line.to_string()))
+            .run()
+            .unwrap();
+        assert!(output.stdout.is_empty());
+        let ours = std::fs::read_link("/proc/self/fd/1").unwrap();
+        let theirs = lines.lock().unwrap()[0].clone();
+        assert!(theirs.starts_with("pipe:"), "{theirs}");
+        assert_ne!(PathBuf::from(theirs), ours);
+    }
+
+    #[test]
+    fn test_line_handlers_propagate_nonzero_exit() {
+        // Emit some output and then exit non-zero
+        let script = r#"
+            printf 'ok\n';
+            printf 'bad\n' 1>&2;
+            exit 3;
+        "#;
+        let res = cmd("sh", ["-c", script])
+            .on_stdout_line(|_| {})
+            .on_stderr_line(|_| {})
+            .run();
+        assert!(res.is_err());
+        let err = format!("{}", res.unwrap_err());
+        assert!(err.contains("sh -c"));
+    }
+
+    #[test]
+    fn test_line_handlers_handle_partial_last_line() {
+        // Emit lines without trailing newline at the end
+        let script = r#"
+            printf 'a1\n';
+            printf 'b1' 1>&2;
+        "#;
+        let out_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let err_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let out_clone = out_lines.clone();
+        let err_clone = err_lines.clone();
+        let output = cmd("sh", ["-c", script])
+            .on_stdout_line(move |line| out_clone.lock().unwrap().push(line.to_string()))
+            .on_stderr_line(move |line| err_clone.lock().unwrap().push(line.to_string()))
+            .run()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(out_lines.lock().unwrap().as_slice(), ["a1"]);
+        assert_eq!(err_lines.lock().unwrap().as_slice(), ["b1"]);
+    }
+
+    #[test]
+    fn test_line_handlers_trim_crlf() {
+        // Ensure CRLF endings are normalized before handler invocation
+        let script = r#"
+            printf 'x1\r\n';
+            printf 'y1\r\n' 1>&2;
+        "#;
+        let out_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let err_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let out_clone = out_lines.clone();
+        let err_clone = err_lines.clone();
+        let output = cmd("sh", ["-c", script])
+            .on_stdout_line(move |line| out_clone.lock().unwrap().push(line.to_string()))
+            .on_stderr_line(move |line| err_clone.lock().unwrap().push(line.to_string()))
+            .run()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(out_lines.lock().unwrap().as_slice(), ["x1"]);
+        assert_eq!(err_lines.lock().unwrap().as_slice(), ["y1"]);
+    }
+
+    #[test]
+    fn test_read_with_handlers_returns_full_stdout_and_invokes_handlers() {
+        let script = r#"
+            printf 'l1\n';
+            printf 'l2\n';
+        "#;
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let out = cmd("sh", ["-c", script])
+            .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.to_string()))
+            .read()
+            .unwrap();
+        assert_eq!(out, "l1\nl2");
+        assert_eq!(lines.lock().unwrap().as_slice(), ["l1", "l2"]);
+    }
+
+    #[test]
+    fn test_read_without_handlers_trims_trailing_newline() {
+        let script = r#"
+            printf 'a\n';
+            printf 'b\n';
+        "#;
+        let out = cmd("sh", ["-c", script]).read().unwrap();
+        assert_eq!(out, "a\nb");
+    }
+
+    #[test]
+    fn test_env() {
+        let out = cmd("sh", ["-c", "echo $TEST_VAR"])
+            .env("TEST_VAR", "hello")
+            .read()
+            .unwrap();
+        assert_eq!(out, "hello");
+    }
+
+    #[test]
+    fn test_envs() {
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("VAR1", "a");
+        vars.insert("VAR2", "b");
+        let out = cmd("sh", ["-c", "echo $VAR1 $VAR2"])
+            .envs(vars)
+            .read()
+            .unwrap();
+        assert_eq!(out, "a b");
+    }
+
+    #[test]
+    fn test_cwd() {
+        let out = cmd("pwd", Vec::<&str>::new()).cwd("/tmp").read().unwrap();
+        // Handle macOS /private/tmp symlink
+        assert!(out.contains("tmp"));
+    }
+
+    #[test]
+    fn test_stdin_bytes() {
+        let out = cmd("cat", Vec::<&str>::new())
+            .stdin_bytes(b"hello stdin")
+            .read()
+            .unwrap();
+        assert_eq!(out, "hello stdin");
+    }
+
+    #[test]
+    fn test_unchecked() {
+        // Without unchecked, this would error
+        let output = cmd("false", Vec::<&str>::new()).unchecked().run().unwrap();
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_unchecked_read() {
+        // Without unchecked, this would error
+        let output = cmd("sh", ["-c", "echo hello; exit 1"])
+            .unchecked()
+            .read()
+            .unwrap();
+        assert_eq!(output, "hello");
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_run_async() {
+        let output = cmd("sh", ["-c", "echo out; echo err >&2"])
+            .stdout_capture()
+            .stderr_capture()
+            .run_async()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        let err = cmd("sh", ["-c", "exit 3"]).run_async().await.unwrap_err();
+        assert!(err.to_string().contains("exited with code 3"));
+        let output = cmd("false", Vec::<&str>::new())
+            .unchecked()
+            .run_async()
+            .await
+            .unwrap();
+        assert!(!output.status.success());
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_read_async() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = cmd("sh", ["-c", "printf '%s %s\\n\\n' \"$A\" \"$(cat)\"; pwd"])
+            .env("A", "a")
+            .stdin_bytes(b"stdin")
+            .cwd(tmp.path())
+            .read_async()
+            .await
+            .unwrap();
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("a stdin"));
+        assert_eq!(lines.next(), Some(""));
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .ends_with(tmp.path().file_name().unwrap().to_str().unwrap())
+        );
+
+        // Large stdin doesn't deadlock against stdout
+        let data = vec![b'x'; 1024 * 1024];
+        let out = cmd("cat", Vec::<&str>::new())
+            .stdin_bytes(&data)
+            .read_async()
+            .await
+            .unwrap();
+        assert_eq!(out.len(), data.len());
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_line_handlers_async() {
+        let out_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let err_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let out_clone = out_lines.clone();
+        let err_clone = err_lines.clone();
+        let expr = cmd("sh", ["-c", "printf 'o1\\r\\no2\\n'; printf 'e1' >&2"])
+            .on_stdout_line(move |line| out_clone.lock().unwrap().push(line.to_string()))
+            .on_stderr_line(move |line| err_clone.lock().unwrap().push(line.to_string()));
+
+        let output = expr.run_async().await.unwrap();
+        assert!(output.stdout.is_empty());
+        assert_eq!(out_lines.lock().unwrap().as_slice(), ["o1", "o2"]);
+        assert_eq!(err_lines.lock().unwrap().as_slice(), ["e1"]);
+
+        assert_eq!(expr.read_async().await.unwrap(), "o1\no2");
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_line_handlers_async_can_block() {
+        use std::sync::mpsc;
+
+        // On this single-threaded runtime, the sender below only runs if the handler
+        // blocks off the runtime's thread
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Mutex::new(rx);
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        let expr = cmd("echo", ["line"]).on_stdout_line(move |_| {
+            let got = rx.lock().unwrap().recv_timeout(Duration::from_secs(5));
+            received_clone.lock().unwrap().push(got.is_ok());
+        });
+        let sender = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            tx.send(()).unwrap();
+        });
+        expr.run_async().await.unwrap();
+        sender.await.unwrap();
+        assert_eq!(*received.lock().unwrap(), [true]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let start = std::time::Instant::now();
+        let err = cmd("sleep", ["10"])
+            .timeout(Duration::from_millis(100))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessTimeout(_, _)));
+        assert!(err.to_string().contains("timed out after 100ms"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Fast commands are unaffected
+        let out = cmd("echo", ["hi"])
+            .timeout(Duration::from_secs(10))
+            .read()
+            .unwrap();
+        assert_eq!(out, "hi");
+    }
+
+    #[test]
+    fn test_timeout_kills_process_group() {
+        // The background sleep holds stdout open, so this only returns if the whole
+        // group is killed
+        let start = std::time::Instant::now();
+        let err = cmd("sh", ["-c", "sleep 10 & sleep 10"])
+            .timeout(Duration::from_millis(100))
+            .read()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessTimeout(_, _)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_timeout_grace_period() {
+        // SIGTERM is ignored, so the process is only stopped by SIGKILL
+        let start = std::time::Instant::now();
+        let err = cmd("sh", ["-c", "trap '' TERM; sleep 10"])
+            .timeout(Duration::from_millis(100))
+            .kill_grace_period(Duration::from_millis(200))
+            .on_stdout_line(|_| {})
+            .unchecked()
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessTimeout(_, _)));
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_cancel_handle() {
+        let cancel = CancelHandle::new();
+        let trigger = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            trigger.cancel();
+        });
+        let err = cmd("sleep", ["10"])
+            .cancel_handle(&cancel)
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessCancelled(_)));
+
+        // Already cancelled
+        let err = cmd("sleep", ["10"])
+            .cancel_handle(&cancel)
+            .read()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessCancelled(_)));
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_timeout_async() {
+        let err = cmd("sh", ["-c", "sleep 10 & sleep 10"])
+            .timeout(Duration::from_millis(100))
+            .read_async()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessTimeout(_, _)));
+
+        let cancel = CancelHandle::new();
+        cancel.cancel();
+        let err = cmd("sleep", ["10"])
+            .cancel_handle(&cancel)
+            .run_async()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessCancelled(_)));
+    }
+
+    #[test]
+    fn test_process_failed() {
+        let err = cmd("sh", ["-c", "echo out; echo oops >&2; exit 3"])
+            .stdout_capture()
+            .stderr_capture()
+            .run()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = &err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(failure.code, Some(3));
+        assert_eq!(failure.signal, None);
+        assert_eq!(failure.stdout, "out");
+        assert_eq!(failure.stderr, "oops");
+        assert_eq!(
+            err.to_string(),
+            format!("exited with code 3\n{}", failure.command)
+        );
+        let help = miette::Diagnostic::help(&err).unwrap().to_string();
+        assert_eq!(help, "stdout:\nout\nstderr:\noops");
+
+        // Only the end of long output is kept
+        let err = cmd("sh", ["-c", "seq 1 100 >&2; exit 1"])
+            .stderr_capture()
+            .read()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.stderr.lines().count(), TAIL_LINES);
+        assert!(failure.stderr.ends_with("99\n100"));
+
+        let err = cmd("sh", ["-c", "kill -9 $$"]).run().unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!((failure.code, failure.signal), (None, Some(9)));
+    }
+
+    #[test]
+    fn test_line_handlers_failure_and_capture() {
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let err = cmd("sh", ["-c", "echo working; echo broken >&2; exit 2"])
+            .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.to_string()))
+            .on_stderr_line(|_| {})
+            .run()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.stdout, "working");
+        assert_eq!(failure.stderr, "broken");
+        assert_eq!(lines.lock().unwrap().as_slice(), ["working"]);
+
+        // With capture, streamed output is also returned
+        let output = cmd("sh", ["-c", "echo a; echo b >&2"])
+            .on_stdout_line(|_| {})
+            .on_stderr_line(|_| {})
+            .stdout_capture()
+            .stderr_capture()
+            .run()
+            .unwrap();
+        assert_eq!(output.stdout, b"a\n");
+        assert_eq!(output.stderr, b"b\n");
+    }
+
+    #[test]
+    fn test_sh_failure() {
+        let err = sh("echo partial; exit 4").unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.code, Some(4));
+        assert_eq!(failure.stdout, "partial");
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_process_failed_async() {
+        let err = cmd("sh", ["-c", "echo oops >&2; exit 5"])
+            .stderr_capture()
+            .run_async()
+            .await
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.code, Some(5));
+        assert_eq!(failure.stderr, "oops");
+    }
+
+    #[test]
+    fn test_display() {
+        let expr = cmd("my prog", ["it's", "$HOME", "--flag=1"])
+            .env("B", "two words")
+            .env("A", "1")
+            .env("NPM_TOKEN", "abc")
+            .env_clear()
+            .cwd("/some dir");
+        assert_eq!(
+            expr.to_shell_string(ShellStyle::Posix),
+            "cd '/some dir' && env -i A=1 B='two words' NPM_TOKEN='[REDACTED]' 'my prog' 'it'\\''s' '$HOME' --flag=1"
+        );
+        assert_eq!(
+            expr.to_shell_string(ShellStyle::PowerShell),
+            "Set-Location -LiteralPath '/some dir'; $env:A = '1'; $env:B = 'two words'; \
+             $env:NPM_TOKEN = '[REDACTED]'; & 'my prog' 'it''s' '$HOME' '--flag=1'"
+        );
+        #[cfg(unix)]
+        assert_eq!(expr.to_string(), expr.to_shell_string(ShellStyle::Posix));
+
+        // Failures show the quoted, redacted command
+        let err = cmd("sh", ["-c", "exit 1", "secret-arg"])
+            .redact("secret-arg")
+            .run()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("sh -c 'exit 1' '[REDACTED]'"),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display_round_trip() {
+        let expr = cmd("printf", ["%s|%s", "it's a \"test\"", "$(echo no)"]).env("X", "a b");
+        let direct = expr.read().unwrap();
+        assert_eq!(sh(&expr.to_string()).unwrap(), direct);
+        assert_eq!(direct, "it's a \"test\"|$(echo no)");
+    }
+
+    #[test]
+    fn test_output_redirects() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out.log");
+        let err = tmp.path().join("err.log");
+        let script = "echo out; echo err >&2";
+        cmd("sh", ["-c", script])
+            .stdout_path(&out)
+            .stderr_path(&err)
+            .run()
+            .unwrap();
+        cmd("sh", ["-c", script])
+            .stdout_append(&out)
+            .stderr_null()
+            .run()
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "out\nout\n");
+        assert_eq!(std::fs::read_to_string(&err).unwrap(), "err\n");
+
+        // Both streams into one file
+        cmd("sh", ["-c", script])
+            .stdout_path(&out)
+            .stderr_to_stdout()
+            .run()
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "out\nerr\n");
+
+        // The last output option wins
+        let output = cmd("echo", ["hi"])
+            .stdout_null()
+            .stdout_capture()
+            .run()
+            .unwrap();
+        assert_eq!(output.stdout, b"hi\n");
+        let output = cmd("echo", ["hi"])
+            .stdout_capture()
+            .stdout_null()
+            .run()
+            .unwrap();
+        assert!(output.stdout.is_empty());
+
+        let err = cmd("echo", ["hi"])
+            .stdout_path(tmp.path().join("missing/out.log"))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::FileError(_, _)));
+    }
+
+    #[test]
+    fn test_stderr_to_stdout() {
+        let script = "echo out; echo err >&2; echo done";
+        let output = cmd("sh", ["-c", script]).stderr_to_stdout().read().unwrap();
+        assert_eq!(output, "out\nerr\ndone");
+
+        // Through the line handler path
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let output = cmd("sh", ["-c", script])
+            .stderr_to_stdout()
+            .stdout_capture()
+            .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.into()))
+            .run()
+            .unwrap();
+        assert_eq!(output.stdout, b"out\nerr\ndone\n");
+        assert_eq!(lines.lock().unwrap().as_slice(), ["out", "err", "done"]);
+
+        // A redirected stream doesn't reach its handler
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("out.log");
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        cmd("sh", ["-c", script])
+            .stdout_path(&path)
+            .on_stdout_line(|_| panic!("stdout is redirected"))
+            .on_stderr_line(move |line| lines_clone.lock().unwrap().push(line.into()))
+            .run()
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\ndone\n");
+        assert_eq!(lines.lock().unwrap().as_slice(), ["err"]);
+
+        // Merged output is in the failure tail
+        let err = cmd("sh", ["-c", "echo oops >&2; exit 2"])
+            .stderr_to_stdout()
+            .read()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.stdout, "oops");
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_redirects_async() {
+        let output = cmd("sh", ["-c", "echo out; echo err >&2"])
+            .stderr_to_stdout()
+            .read_async()
+            .await
+            .unwrap();
+        assert_eq!(output, "out\nerr");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("err.log");
+        let output = cmd("sh", ["-c", "echo out; echo err >&2"])
+            .stdout_null()
+            .stderr_path(&path)
+            .run_async()
+            .await
+            .unwrap();
+        assert!(output.stdout.is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "err\n");
+    }
+}
@@ -0,0 +1,362 @@
+//! Pipelines between commands
+//!
+//! [`XXExpression::pipe`] connects the stdout of one command to the stdin of the next,
+//! like `a | b | c` in a shell but without a shell, so arguments never need quoting.
+//! Each stage keeps its own environment, working directory, stderr handling and
+//! `unchecked` setting.
+//!
+//! Pipelines have `pipefail` semantics: the pipeline fails if any checked stage fails,
+//! and the error names the rightmost stage that did. Stdin comes from the first stage
+//! and stdout is handled by the last stage's settings; stdout redirects on earlier
+//! stages are ignored, but [`stderr_to_stdout`](XXExpression::stderr_to_stdout) sends a
+//! stage's stderr down the pipe along with its stdout.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::process;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! let count = process::cmd("git", ["ls-files"])
+//!     .pipe(process::cmd("grep", ["\\.rs$"]))
+//!     .pipe(process::cmd("wc", ["-l"]))
+//!     .read()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::process::{ExitStatus, Output, Stdio};
+use std::thread;
+use std::{fmt, io};
+
+use super::{
+    ShellStyle, StreamedOutput, XXExpression, forward_output, process_failure, read_output,
+};
+use crate::{XXError, XXResult};
+
+/// Commands connected stdout-to-stdin, created with [`XXExpression::pipe`]
+pub struct XXPipeline {
+    stages: Vec<XXExpression>,
+}
+
+impl XXExpression {
+    /// Pipe this command's stdout into the stdin of `next`
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process;
+    /// let output = process::cmd("printf", ["b\\na\\n"])
+    ///     .pipe(process::cmd("sort", Vec::<&str>::new()))
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "a\nb");
+    /// ```
+    pub fn pipe(self, next: XXExpression) -> XXPipeline {
+        XXPipeline {
+            stages: vec![self, next],
+        }
+    }
+}
+
+impl XXPipeline {
+    /// Pipe the output of the last stage into the stdin of `next`
+    pub fn pipe(mut self, next: XXExpression) -> Self {
+        self.stages.push(next);
+        self
+    }
+
+    /// Run the pipeline
+    ///
+    /// The returned status is that of the rightmost checked stage that failed, or of
+    /// the last stage if none did. `stdout` is captured if the last stage has
+    /// [`stdout_capture`](XXExpression::stdout_capture) set, and `stderr` holds the
+    /// captured stderr of every stage with
+    /// [`stderr_capture`](XXExpression::stderr_capture) set, in stage order.
+    pub fn run(&self) -> XXResult<Output> {
+        debug!("$ {self}");
+        self.execute(self.last().stdout_capture)
+    }
+
+    /// Run the pipeline and return the stdout of the last stage, with trailing newlines
+    /// trimmed
+    pub fn read(&self) -> XXResult<String> {
+        debug!("$ {self}");
+        let output = self.execute(true)?;
+        self.last().stdout_string(output.stdout)
+    }
+
+    /// The pipeline quoted for `style`; see [`XXExpression::to_shell_string`]
+    pub fn to_shell_string(&self, style: ShellStyle) -> String {
+        self.stages
+            .iter()
+            .map(|stage| stage.shell_string(style, true))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn last(&self) -> &XXExpression {
+        self.stages
+            .last()
+            .expect("a pipeline has at least two stages")
+    }
+
+    fn execute(&self, capture_stdout: bool) -> XXResult<Output> {
+        let last_index = self.stages.len() - 1;
+        let mut children: Vec<std::process::Child> = Vec::with_capacity(self.stages.len());
+        let mut watchdogs = Vec::with_capacity(self.stages.len());
+        let mut guards = Vec::with_capacity(self.stages.len());
+        let mut stderr_handles = Vec::with_capacity(self.stages.len());
+        let mut stdout_handle = None;
+        let mut stdin_handle = None;
+        let mut previous_stdout = None;
+
+        for (i, stage) in self.stages.iter().enumerate() {
+            let is_last = i == last_index;
+            let mut cmd = stage.command();
+            match previous_stdout.take() {
+                Some(stdout) => cmd.stdin(stdout),
+                None if stage.stdin_data.is_some() => cmd.stdin(Stdio::piped()),
+                None => cmd.stdin(Stdio::inherit()),
+            };
+            let spawned = stage
+                .output_stdio(!is_last || capture_stdout)
+                .and_then(|output| {
+                    cmd.stdout(output.stdout).stderr(output.stderr);
+                    let child = cmd
+                        .spawn()
+                        .map_err(|err| XXError::ProcessError(err, stage.to_string()))?;
+                    Ok((child, output.merged))
+                });
+            // Close our copies of the merged pipe's write end so reading it can finish
+            drop(cmd);
+            let (mut child, merged) = match spawned {
+                Ok(spawned) => spawned,
+                Err(err) => {
+                    // Don't leave earlier stages running with nowhere to write
+                    for mut child in children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(err);
+                }
+            };
+            watchdogs.push(stage.limits.watch(child.id()));
+            guards.push(stage.group_guard(child.id()));
+
+            if let (Some(mut stdin), Some(data)) = (child.stdin.take(), stage.stdin_data.clone()) {
+                // Written on a separate thread so a large input can't deadlock the pipeline
+                stdin_handle = Some(thread::spawn(move || {
+                    use std::io::Write;
+                    let _ = stdin.write_all(&data);
+                }));
+            }
+            if !is_last {
+                previous_stdout = match merged {
+                    Some(merged) => Some(Stdio::from(merged)),
+                    None => child.stdout.take().map(Stdio::from),
+                };
+            } else {
+                let handler = stage.stdout_handler.clone();
+                stdout_handle = match (merged, child.stdout.take()) {
+                    (Some(merged), _) => Some(thread::spawn(move || {
+                        read_output(merged, handler, capture_stdout)
+                    })),
+                    (None, Some(stdout)) => Some(thread::spawn(move || {
+                        read_output(stdout, handler, capture_stdout)
+                    })),
+                    (None, None) => None,
+                };
+            }
+            stderr_handles.push(child.stderr.take().map(|stderr| {
+                let handler = stage.stderr_handler.clone();
+                let capture = stage.stderr_capture;
+                let forward = stage.forward_stderr();
+                thread::spawn(move || match forward {
+                    true => forward_output(stderr, io::stderr()),
+                    false => read_output(stderr, handler, capture),
+                })
+            }));
+            children.push(child);
+        }
+
+        let statuses: Vec<_> = children.iter_mut().map(|child| child.wait()).collect();
+        if let Some(h) = stdin_handle {
+            let _ = h.join();
+        }
+        let stdout = stdout_handle
+            .map(|h| h.join().unwrap_or_default())
+            .unwrap_or_default();
+        let stderrs: Vec<StreamedOutput> = stderr_handles
+            .into_iter()
+            .map(|h| h.map(|h| h.join().unwrap_or_default()).unwrap_or_default())
+            .collect();
+        for (stage, watchdog) in self.stages.iter().zip(watchdogs) {
+            stage.check_stopped(watchdog)?;
+        }
+
+        let mut statuses = self
+            .stages
+            .iter()
+            .zip(statuses)
+            .map(|(stage, status)| {
+                status.map_err(|err| XXError::ProcessError(err, stage.to_string()))
+            })
+            .collect::<XXResult<Vec<ExitStatus>>>()?;
+        for (guard, status) in guards.into_iter().zip(&statuses) {
+            if let Some(guard) = guard {
+                guard.exited(status.success());
+            }
+        }
+        let failed = (0..statuses.len())
+            .rev()
+            .find(|&i| !statuses[i].success() && !self.stages[i].unchecked);
+        if let Some(i) = failed {
+            let stdout_tail = match i == last_index {
+                true => stdout.tail(),
+                false => String::new(),
+            };
+            let stage = &self.stages[i];
+            let mut failure = process_failure(
+                stage.to_string(),
+                statuses[i],
+                stdout_tail,
+                stderrs[i].tail(),
+            );
+            failure.pipeline = Some(self.to_string());
+            return Err(XXError::ProcessFailed(Box::new(failure)));
+        }
+
+        Ok(Output {
+            status: statuses.swap_remove(last_index),
+            stdout: stdout.captured,
+            stderr: stderrs.into_iter().flat_map(|s| s.captured).collect(),
+        })
+    }
+}
+
+impl fmt::Display for XXPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_shell_string(ShellStyle::native()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::super::cmd;
+    use super::*;
+
+    #[test]
+    fn test_pipe() {
+        let output = cmd("sh", ["-c", "echo $GREETING; echo skip; pwd"])
+            .env("GREETING", "hello world")
+            .cwd("/")
+            .pipe(cmd("grep", ["-v", "skip"]))
+            .pipe(cmd("tr", ["a-z", "A-Z"]))
+            .read()
+            .unwrap();
+        assert_eq!(output, "HELLO WORLD\n/");
+
+        // Arguments aren't interpreted by a shell
+        let output = cmd("echo", ["$HOME; rm -rf /"])
+            .pipe(cmd("cat", Vec::<&str>::new()))
+            .read()
+            .unwrap();
+        assert_eq!(output, "$HOME; rm -rf /");
+    }
+
+    #[test]
+    fn test_pipe_stdin_and_capture() {
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let output = cmd("sort", Vec::<&str>::new())
+            .stdin_bytes("c\nb\na\n")
+            .pipe(cmd("sh", ["-c", "cat; echo done >&2"]).stderr_capture())
+            .pipe(
+                cmd("head", ["-n", "2"])
+                    .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.into())),
+            )
+            .run()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.stderr, b"done\n");
+        assert_eq!(lines.lock().unwrap().as_slice(), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_pipefail() {
+        let pipeline = cmd("sh", ["-c", "echo bad input >&2; exit 3"])
+            .stderr_capture()
+            .pipe(cmd("cat", Vec::<&str>::new()))
+            .pipe(cmd("wc", ["-l"]));
+        let err = pipeline.read().unwrap_err();
+        let XXError::ProcessFailed(failure) = &err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(failure.code, Some(3));
+        assert!(failure.command.starts_with("sh -c"));
+        assert_eq!(failure.stderr, "bad input");
+        assert_eq!(
+            failure.pipeline.as_deref(),
+            Some(pipeline.to_string().as_str())
+        );
+        assert!(err.to_string().contains("in pipeline: sh -c"));
+
+        // The rightmost failing stage is reported
+        let err = cmd("false", Vec::<&str>::new())
+            .pipe(cmd("sh", ["-c", "cat; exit 4"]))
+            .run()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.code, Some(4));
+
+        // Unchecked stages are ignored
+        let output = cmd("false", Vec::<&str>::new())
+            .unchecked()
+            .pipe(cmd("echo", ["ok"]))
+            .read()
+            .unwrap();
+        assert_eq!(output, "ok");
+    }
+
+    #[test]
+    fn test_pipe_display() {
+        let pipeline = cmd("echo", ["a b"])
+            .cwd("/tmp")
+            .pipe(cmd("grep", ["a"]).env("LC_ALL", "C"));
+        assert_eq!(
+            pipeline.to_shell_string(ShellStyle::Posix),
+            "(cd /tmp && echo 'a b') | LC_ALL=C grep a"
+        );
+        assert_eq!(
+            pipeline.to_shell_string(ShellStyle::PowerShell),
+            "& { Set-Location -LiteralPath /tmp; echo 'a b' } | & { $env:LC_ALL = 'C'; grep a }"
+        );
+    }
+
+    #[test]
+    fn test_pipe_spawn_error() {
+        let err = cmd("sleep", ["10"])
+            .pipe(cmd("/nonexistent/command", Vec::<&str>::new()))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessError(_, _)));
+        assert!(err.to_string().contains("/nonexistent/command"));
+    }
+
+    #[test]
+    fn test_pipe_stderr_to_stdout() {
+        let output = cmd("sh", ["-c", "echo b; echo a >&2"])
+            .stderr_to_stdout()
+            .pipe(cmd("sort", Vec::<&str>::new()))
+            .read()
+            .unwrap();
+        assert_eq!(output, "a\nb");
+    }
+}
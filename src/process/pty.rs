@@ -0,0 +1,365 @@
+//! Running commands under a pseudo-terminal
+//!
+//! Many tools only print colors, progress bars or prompts when their output is a
+//! terminal. With [`XXExpression::pty`](super::XXExpression::pty) (Unix only, with the
+//! `process_unix` feature), the command's stdin, stdout and stderr are all connected
+//! to a new pseudo-terminal, so `isatty` is true and the terminal size is what
+//! [`pty_size`](super::XXExpression::pty_size) says.
+//!
+//! Everything the command writes arrives as one stream, which goes to the
+//! [`on_stdout_line`](super::XXExpression::on_stdout_line) handler and to captured
+//! stdout, or to this process's stdout if neither is set. Lines end in `\r\n` on a
+//! terminal; the `\r` is removed as usual. ANSI escape codes are kept unless
+//! [`pty_strip_ansi`](super::XXExpression::pty_strip_ansi) is set.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::process;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! let output = process::cmd("cargo", ["build", "--color=auto"])
+//!     .pty()
+//!     .pty_size(40, 120)
+//!     .pty_strip_ansi()
+//!     .read()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Read};
+
+/// Default terminal size, in rows and columns
+pub(crate) const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+/// Pseudo-terminal settings for an expression
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PtyConfig {
+    pub(crate) rows: u16,
+    pub(crate) cols: u16,
+    pub(crate) strip_ansi: bool,
+}
+
+impl Default for PtyConfig {
+    fn default() -> Self {
+        Self {
+            rows: DEFAULT_SIZE.0,
+            cols: DEFAULT_SIZE.1,
+            strip_ansi: false,
+        }
+    }
+}
+
+/// Removes ANSI escape sequences from a byte stream
+///
+/// Handles CSI sequences (`ESC [ ... final`), OSC sequences (`ESC ] ... BEL` or
+/// `ESC ] ... ESC \`) and other escapes such as `ESC ( B`, even when split across
+/// reads.
+#[cfg_attr(not(all(unix, feature = "process_unix")), allow(dead_code))]
+pub(crate) struct StripAnsi<R> {
+    inner: R,
+    state: AnsiState,
+}
+
+#[cfg_attr(not(all(unix, feature = "process_unix")), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AnsiState {
+    Text,
+    Escape,
+    /// Between `ESC` and the final byte of a sequence such as `ESC ( B`
+    Intermediate,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+#[cfg_attr(not(all(unix, feature = "process_unix")), allow(dead_code))]
+impl<R> StripAnsi<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: AnsiState::Text,
+        }
+    }
+
+    /// Filter `buf` in place, returning the number of bytes kept
+    fn strip(&mut self, buf: &mut [u8]) -> usize {
+        let mut kept = 0;
+        for i in 0..buf.len() {
+            let b = buf[i];
+            self.state = match (self.state, b) {
+                (AnsiState::Text, 0x1b) => AnsiState::Escape,
+                (AnsiState::Text, _) => {
+                    buf[kept] = b;
+                    kept += 1;
+                    AnsiState::Text
+                }
+                (AnsiState::Escape, b'[') => AnsiState::Csi,
+                (AnsiState::Escape, b']') => AnsiState::Osc,
+                (AnsiState::Escape | AnsiState::Intermediate, 0x20..=0x2f) => {
+                    AnsiState::Intermediate
+                }
+                (AnsiState::Escape | AnsiState::Intermediate, _) => AnsiState::Text,
+                (AnsiState::Csi, 0x40..=0x7e) => AnsiState::Text,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+                (AnsiState::Osc, 0x07) => AnsiState::Text,
+                (AnsiState::Osc, 0x1b) => AnsiState::OscEscape,
+                (AnsiState::Osc, _) => AnsiState::Osc,
+                (AnsiState::OscEscape, b'\\') => AnsiState::Text,
+                (AnsiState::OscEscape, _) => AnsiState::Osc,
+            };
+        }
+        kept
+    }
+}
+
+impl<R: Read> Read for StripAnsi<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let kept = self.strip(&mut buf[..n]);
+            // Returning 0 would look like the end of the stream
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "process_unix"))]
+pub(crate) use unix::run_pty;
+
+#[cfg(all(unix, feature = "process_unix"))]
+mod unix {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::fd::FromRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::process::CommandExt;
+    use std::process::Output;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use super::{PtyConfig, StripAnsi};
+    use crate::process::{XXExpression, forward_output, read_lines};
+    use crate::{XXError, XXResult};
+
+    /// `ptsname` isn't thread-safe
+    static PTSNAME: Mutex<()> = Mutex::new(());
+
+    /// The controlling side of a pseudo-terminal
+    struct Master(File);
+
+    impl Read for Master {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf) {
+                // Linux reports EIO once every process has closed the terminal
+                Err(err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+                result => result,
+            }
+        }
+    }
+
+    /// Open a pseudo-terminal of the given size, returning its master and slave
+    fn open(config: &PtyConfig) -> io::Result<(Master, File)> {
+        // SAFETY: these calls are given a valid, owned file descriptor and a
+        // correctly initialized winsize; ptsname's static buffer is guarded by PTSNAME
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let size = libc::winsize {
+                ws_row: config.rows,
+                ws_col: config.cols,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            if libc::ioctl(fd, libc::TIOCSWINSZ, &size) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let path = {
+                let _lock = PTSNAME.lock().unwrap();
+                let name = libc::ptsname(fd);
+                if name.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                std::ffi::CStr::from_ptr(name)
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            let slave = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_NOCTTY)
+                .open(path)?;
+            Ok((Master(master), slave))
+        }
+    }
+
+    /// Run `expr` with a pseudo-terminal as its stdin, stdout and stderr
+    pub(crate) fn run_pty(
+        expr: &XXExpression,
+        config: &PtyConfig,
+        capture_stdout: bool,
+    ) -> XXResult<Output> {
+        let to_err = |err| XXError::ProcessError(err, expr.to_string());
+        if expr.stdin_data.is_some() {
+            return Err(crate::error!(
+                "stdin_bytes can't be used with pty, which gives the command a terminal as stdin: {expr}"
+            ));
+        }
+        let (master, slave) = open(config).map_err(to_err)?;
+        let mut cmd = expr.command();
+        cmd.stdin(slave.try_clone().map_err(to_err)?)
+            .stdout(slave.try_clone().map_err(to_err)?)
+            .stderr(slave);
+        // SAFETY: setsid and ioctl are async-signal-safe
+        unsafe {
+            cmd.pre_exec(|| {
+                // A new session, which is also a new process group, with the terminal as
+                // its controlling terminal
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let mut child = cmd.spawn().map_err(to_err)?;
+        // Close our copies of the slave so reading the master ends when the child exits
+        drop(cmd);
+        let watchdog = expr.limits.watch(child.id());
+        let guard = expr.group_guard(child.id());
+
+        let handler = expr.stdout_handler.clone();
+        let strip_ansi = config.strip_ansi;
+        let reader = thread::spawn(move || {
+            let master: Box<dyn Read + Send> = match strip_ansi {
+                true => Box::new(StripAnsi::new(master)),
+                false => Box::new(master),
+            };
+            match handler.is_none() && !capture_stdout {
+                true => forward_output(master, io::stdout()),
+                false => read_lines(master, handler, capture_stdout),
+            }
+        });
+
+        let status = child.wait().map_err(to_err)?;
+        let stdout = reader.join().unwrap_or_default();
+        expr.check_stopped(watchdog)?;
+        if let Some(guard) = guard {
+            guard.exited(status.success());
+        }
+        expr.check_exit(status, || (stdout.tail(), String::new()))?;
+        Ok(Output {
+            status,
+            stdout: stdout.captured,
+            stderr: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[cfg(all(unix, feature = "process_unix"))]
+    #[test]
+    fn test_pty() {
+        use std::sync::{Arc, Mutex};
+
+        use super::super::cmd;
+
+        let script = "test -t 0 && test -t 1 && test -t 2 && echo tty; stty size; printf '\\033[31mred\\033[0m\\n'";
+        let output = cmd("sh", ["-c", script]).pty().read().unwrap();
+        assert_eq!(output, "tty\n24 80\n\x1b[31mred\x1b[0m");
+
+        let lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let output = cmd("sh", ["-c", script])
+            .pty_size(40, 120)
+            .pty_strip_ansi()
+            .stdout_capture()
+            .on_stdout_line(move |line| lines_clone.lock().unwrap().push(line.into()))
+            .run()
+            .unwrap();
+        assert_eq!(output.stdout, b"tty\n40 120\nred\n");
+        assert_eq!(lines.lock().unwrap().as_slice(), ["tty", "40 120", "red"]);
+
+        // Without a pty, output isn't a terminal
+        let output = cmd("sh", ["-c", "test -t 1 || echo pipe"]).read().unwrap();
+        assert_eq!(output, "pipe");
+    }
+
+    #[cfg(all(unix, feature = "process_unix"))]
+    #[test]
+    fn test_pty_failure_and_timeout() {
+        use std::time::{Duration, Instant};
+
+        use super::super::cmd;
+        use crate::XXError;
+
+        let err = cmd("sh", ["-c", "echo oops >&2; exit 4"])
+            .pty()
+            .run()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.code, Some(4));
+        assert_eq!(failure.stdout, "oops");
+
+        let start = Instant::now();
+        let err = cmd("sleep", ["10"])
+            .pty()
+            .timeout(Duration::from_millis(100))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessTimeout(..)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let err = cmd("cat", Vec::<&str>::new())
+            .pty()
+            .stdin_bytes("x")
+            .run()
+            .unwrap_err();
+        assert!(err.to_string().contains("can't be used with pty"));
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let input = b"\x1b[1;31mred\x1b[0m \x1b]0;title\x07plain \x1b]8;;url\x1b\\link\x1b(B";
+        let mut output = String::new();
+        StripAnsi::new(&input[..])
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "red plain link");
+
+        // Sequences split across reads
+        struct OneByte<'a>(&'a [u8]);
+        impl Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let Some((first, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = *first;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+        let mut output = String::new();
+        StripAnsi::new(OneByte(b"a\x1b[38;5;208mb\x1b[0m"))
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "ab");
+    }
+}
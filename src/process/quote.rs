@@ -0,0 +1,204 @@
+//! Shell quoting and redaction for displaying commands
+//!
+//! [`XXExpression`](super::XXExpression) and [`XXPipeline`](super::XXPipeline) display
+//! as a command line that can be pasted into a shell, including environment overrides
+//! and the working directory. Secrets are masked: values of environment variables
+//! whose names look like credentials (`GITHUB_TOKEN`, `DB_PASSWORD`, ...), values
+//! registered with [`redact`] and values passed to
+//! [`XXExpression::redact`](super::XXExpression::redact) are shown as `[REDACTED]`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use xx::process;
+//!
+//! let expr = process::cmd("curl", ["-H", "Authorization: Bearer s3cret", "https://example.com"])
+//!     .env("GITHUB_TOKEN", "ghp_abc")
+//!     .redact("s3cret");
+//! assert_eq!(
+//!     expr.to_shell_string(process::ShellStyle::Posix),
+//!     "GITHUB_TOKEN='[REDACTED]' curl -H 'Authorization: Bearer [REDACTED]' https://example.com"
+//! );
+//! ```
+
+use std::borrow::Cow;
+use std::sync::RwLock;
+
+use crate::redact::{REDACTED, is_sensitive};
+
+/// Secrets registered with [`redact`]
+static REDACTIONS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Which shell a command line is quoted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellStyle {
+    /// `sh`, `bash`, `zsh` and other POSIX shells
+    Posix,
+    /// PowerShell
+    PowerShell,
+}
+
+impl ShellStyle {
+    /// PowerShell on Windows, POSIX everywhere else
+    pub fn native() -> Self {
+        match cfg!(windows) {
+            true => Self::PowerShell,
+            false => Self::Posix,
+        }
+    }
+
+    /// Quote `arg` so this shell reads it as a single word
+    ///
+    /// ```
+    /// use xx::process::ShellStyle;
+    /// assert_eq!(ShellStyle::Posix.quote("it's"), r#"'it'\''s'"#);
+    /// assert_eq!(ShellStyle::PowerShell.quote("it's"), "'it''s'");
+    /// assert_eq!(ShellStyle::Posix.quote("plain-word"), "plain-word");
+    /// ```
+    pub fn quote(self, arg: &str) -> String {
+        if !arg.is_empty() && arg.chars().all(|c| is_safe(self, c)) {
+            return arg.to_string();
+        }
+        self.quote_always(arg)
+    }
+
+    /// Quote `arg` even if it doesn't need it, for PowerShell values in expression mode
+    pub(crate) fn quote_always(self, arg: &str) -> String {
+        match self {
+            Self::Posix => format!("'{}'", arg.replace('\'', r"'\''")),
+            // PowerShell also treats typographic quotes as quotes
+            Self::PowerShell => {
+                let mut quoted = String::with_capacity(arg.len() + 2);
+                quoted.push('\'');
+                for c in arg.chars() {
+                    if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+                        quoted.push(c);
+                    }
+                    quoted.push(c);
+                }
+                quoted.push('\'');
+                quoted
+            }
+        }
+    }
+}
+
+fn is_safe(style: ShellStyle, c: char) -> bool {
+    match style {
+        ShellStyle::Posix => c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c),
+        ShellStyle::PowerShell => c.is_ascii_alphanumeric() || "_-+./\\:".contains(c),
+    }
+}
+
+/// Quote `arg` for a POSIX shell
+///
+/// # Example
+/// ```
+/// use xx::process;
+/// assert_eq!(process::shell_quote("hello world"), "'hello world'");
+/// assert_eq!(process::shell_quote("--flag=1"), "--flag=1");
+/// assert_eq!(process::shell_quote(""), "''");
+/// ```
+pub fn shell_quote(arg: &str) -> String {
+    ShellStyle::Posix.quote(arg)
+}
+
+/// Mask `secret` wherever it appears in displayed commands, for every command
+///
+/// Use this for tokens read at startup so they never reach logs or error messages.
+pub fn redact(secret: impl Into<String>) {
+    let secret = secret.into();
+    if secret.is_empty() {
+        return;
+    }
+    let mut redactions = REDACTIONS.write().unwrap();
+    if !redactions.contains(&secret) {
+        redactions.push(secret);
+    }
+}
+
+/// Replace registered secrets and `extra` in `s` with `[REDACTED]`
+pub(crate) fn redact_str<'a>(s: &'a str, extra: &[String]) -> Cow<'a, str> {
+    let redactions = REDACTIONS.read().unwrap();
+    let mut s = Cow::Borrowed(s);
+    for secret in redactions.iter().chain(extra) {
+        if !secret.is_empty() && s.contains(secret.as_str()) {
+            s = Cow::Owned(s.replace(secret.as_str(), REDACTED));
+        }
+    }
+    s
+}
+
+/// The displayed value of an environment variable
+pub(crate) fn env_value<'a>(name: &str, value: &'a str, extra: &[String]) -> Cow<'a, str> {
+    match is_sensitive(name) {
+        true => Cow::Borrowed(REDACTED),
+        false => redact_str(value, extra),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        for (arg, posix, powershell) in [
+            ("abc", "abc", "abc"),
+            ("", "''", "''"),
+            ("a b", "'a b'", "'a b'"),
+            ("$HOME", "'$HOME'", "'$HOME'"),
+            ("it's", r"'it'\''s'", "'it''s'"),
+            ("a;b&&c|d", "'a;b&&c|d'", "'a;b&&c|d'"),
+            ("key=value", "key=value", "'key=value'"),
+            ("C:\\dir", "'C:\\dir'", "C:\\dir"),
+            ("`x`", "'`x`'", "'`x`'"),
+            ("\u{2019}", "'\u{2019}'", "'\u{2019}\u{2019}'"),
+        ] {
+            assert_eq!(ShellStyle::Posix.quote(arg), posix, "{arg}");
+            assert_eq!(ShellStyle::PowerShell.quote(arg), powershell, "{arg}");
+        }
+    }
+
+    #[test]
+    fn test_posix_round_trip() {
+        let args = [
+            "plain",
+            "with space",
+            "it's",
+            "$(rm -rf /)",
+            "\"double\"",
+            "",
+        ];
+        let script = format!(
+            "printf '%s\\n' {}",
+            args.iter()
+                .map(|a| shell_quote(a))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let output = std::process::Command::new("sh")
+            .args(["-c", &script])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            args.join("\n") + "\n"
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        redact("hunter2-test-secret");
+        assert_eq!(
+            redact_str("--password=hunter2-test-secret", &[]),
+            "--password=[REDACTED]"
+        );
+        assert_eq!(redact_str("a-b", &["b".to_string()]), "a-[REDACTED]");
+        assert_eq!(env_value("API_KEY", "abc", &[]), REDACTED);
+        assert_eq!(env_value("AWS_SIGNATURE", "abc", &[]), REDACTED);
+        assert_eq!(env_value("PATH", "/bin", &[]), "/bin");
+    }
+}
@@ -0,0 +1,304 @@
+//! Retrying commands that fail transiently
+//!
+//! [`XXExpression::retry`] runs a failed command again with exponential backoff, the
+//! same schedule as the HTTP client's retries: the first retry waits the given
+//! backoff, each one after that waits twice as long, up to [`MAX_RETRY_DELAY`].
+//! [`XXExpression::retry_if`] limits retries to failures that look transient, based
+//! on the exit code and the end of the output.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use xx::process;
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! process::cmd("git", ["fetch", "origin"])
+//!     .retry(3, Duration::from_secs(1))
+//!     .retry_if(|failure| failure.stderr.contains("Could not resolve host"))
+//!     .run()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::XXExpression;
+use crate::error::ProcessFailure;
+use crate::{XXError, XXResult};
+
+pub use crate::backoff::MAX_RETRY_DELAY;
+
+type RetryPredicate = dyn Fn(&ProcessFailure) -> bool + Send + Sync + 'static;
+
+/// Retry settings for an expression
+#[derive(Clone, Default)]
+pub(crate) struct Retry {
+    retries: u32,
+    backoff: Duration,
+    predicate: Option<Arc<RetryPredicate>>,
+}
+
+impl Retry {
+    /// Whether stderr has to be read, even if it isn't captured, to check the predicate
+    pub(crate) fn needs_stderr(&self) -> bool {
+        self.retries > 0 && self.predicate.is_some()
+    }
+
+    /// How long to wait before retry number `attempt` after `err`, or `None` to give up
+    fn delay(&self, err: &XXError, attempt: u32) -> Option<Duration> {
+        let XXError::ProcessFailed(failure) = err else {
+            return None;
+        };
+        if attempt > self.retries || self.predicate.as_ref().is_some_and(|p| !p(failure)) {
+            return None;
+        }
+        Some(crate::backoff::retry_delay(self.backoff, attempt))
+    }
+}
+
+impl XXExpression {
+    /// Run the command up to `retries` more times if it fails, waiting `backoff` before
+    /// the first retry and doubling the wait each time after, up to [`MAX_RETRY_DELAY`]
+    ///
+    /// Only unsuccessful exits ([`XXError::ProcessFailed`]) are retried, not commands
+    /// that can't be started, time out or are cancelled. Line handlers see the output
+    /// of every attempt. Applies to [`run`](Self::run), [`read`](Self::read) and their
+    /// async versions.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use xx::process;
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let marker = tmp.path().join("marker");
+    /// // Fails the first time, succeeds the second
+    /// let output = process::cmd("sh", ["-c", "test -e \"$1\" || { touch \"$1\"; exit 1; }; echo ok", "sh"])
+    ///     .arg(&marker)
+    ///     .retry(2, Duration::from_millis(10))
+    ///     .read()
+    ///     .unwrap();
+    /// assert_eq!(output, "ok");
+    /// ```
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retry.retries = retries;
+        self.retry.backoff = backoff;
+        self
+    }
+
+    /// Only retry failures for which `predicate` returns true
+    ///
+    /// The predicate sees the exit code and the last lines of stdout and stderr. So that
+    /// `failure.stderr` is always available, stderr that would be inherited is piped
+    /// instead and copied to this process's stderr unchanged. The command's stderr is
+    /// then not a terminal, so tools that check `isatty` may print it without colors or
+    /// progress bars.
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&ProcessFailure) -> bool + Send + Sync + 'static,
+    {
+        self.retry.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Call `f` until it succeeds or the retry settings give up
+    pub(crate) fn with_retry<T>(&self, mut f: impl FnMut() -> XXResult<T>) -> XXResult<T> {
+        let mut attempt = 0;
+        loop {
+            let err = match f() {
+                Ok(out) => return Ok(out),
+                Err(err) => err,
+            };
+            attempt += 1;
+            let Some(delay) = self.retry.delay(&err, attempt) else {
+                return Err(err);
+            };
+            self.log_retry(&err, attempt, delay);
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Async version of [`with_retry`](Self::with_retry)
+    #[cfg(feature = "process_async")]
+    pub(crate) async fn with_retry_async<T, F, Fut>(&self, mut f: F) -> XXResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = XXResult<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let err = match f().await {
+                Ok(out) => return Ok(out),
+                Err(err) => err,
+            };
+            attempt += 1;
+            let Some(delay) = self.retry.delay(&err, attempt) else {
+                return Err(err);
+            };
+            self.log_retry(&err, attempt, delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn log_retry(&self, err: &XXError, attempt: u32, delay: Duration) {
+        let reason = err.to_string();
+        let reason = reason.lines().next().unwrap_or_default();
+        debug!(
+            "retry {attempt}/{} of {self} in {delay:?}: {reason}",
+            self.retry.retries
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use pretty_assertions::assert_eq;
+
+    use super::super::cmd;
+    use super::*;
+
+    /// A script that fails with `code` until it has run `failures` times
+    fn flaky(dir: &std::path::Path, failures: usize, code: i32) -> XXExpression {
+        let counter = dir.join("count");
+        let script = format!(
+            "n=$(cat \"$1\" 2>/dev/null || echo 0); echo $((n + 1)) > \"$1\"; \
+             if [ \"$n\" -lt {failures} ]; then echo \"attempt $n failed\" >&2; exit {code}; fi; echo done"
+        );
+        cmd("sh", ["-c", script.as_str(), "sh"]).arg(counter)
+    }
+
+    #[test]
+    fn test_retry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lines = Arc::new(Mutex::new(vec![]));
+        let lines_clone = lines.clone();
+        let output = flaky(tmp.path(), 2, 1)
+            .retry(3, Duration::from_millis(1))
+            .on_stderr_line(move |line| lines_clone.lock().unwrap().push(line.to_string()))
+            .read()
+            .unwrap();
+        assert_eq!(output, "done");
+        assert_eq!(
+            *lines.lock().unwrap(),
+            ["attempt 0 failed", "attempt 1 failed"]
+        );
+
+        // Gives up after the last retry with the last failure
+        let tmp = tempfile::tempdir().unwrap();
+        let err = flaky(tmp.path(), 5, 1)
+            .retry(2, Duration::from_millis(1))
+            .stderr_capture()
+            .run()
+            .unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.stderr, "attempt 2 failed");
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("count")).unwrap(),
+            "3\n"
+        );
+    }
+
+    #[test]
+    fn test_retry_if() {
+        // Stderr isn't captured, but the predicate still sees it
+        let tmp = tempfile::tempdir().unwrap();
+        let output = flaky(tmp.path(), 1, 128)
+            .retry(3, Duration::from_millis(1))
+            .retry_if(|failure| failure.code == Some(128) && failure.stderr.contains("failed"))
+            .read()
+            .unwrap();
+        assert_eq!(output, "done");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let err = flaky(tmp.path(), 1, 2)
+            .retry(3, Duration::from_millis(1))
+            .retry_if(|failure| failure.code == Some(128))
+            .read()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessFailed(_)));
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("count")).unwrap(),
+            "1\n"
+        );
+
+        // Commands that can't start aren't retried
+        let err = cmd("/nonexistent/command", Vec::<&str>::new())
+            .retry(3, Duration::from_secs(10))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessError(..)));
+    }
+
+    #[test]
+    fn test_retry_if_forwards_stderr() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen_clone = seen.clone();
+        let err = cmd("sh", ["-c", "printf 'a\\n\\n\\033[31mb\\n' >&2; exit 1"])
+            .retry(1, Duration::from_millis(1))
+            .retry_if(move |failure| {
+                seen_clone.lock().unwrap().push(failure.stderr.clone());
+                true
+            })
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, XXError::ProcessFailed(_)));
+        assert_eq!(*seen.lock().unwrap(), ["a\n\n\x1b[31mb"]);
+
+        let input = b"a\n\n\x1b[31mb\r\n\xff";
+        let mut out = vec![];
+        let output = super::super::forward_output(&input[..], &mut out);
+        assert_eq!(out, input);
+        assert_eq!(output.tail(), "a\n\n\x1b[31mb\n\u{fffd}");
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = Retry {
+            retries: 10,
+            backoff: Duration::from_millis(500),
+            predicate: None,
+        };
+        let err = crate::error!("not a process failure");
+        assert_eq!(retry.delay(&err, 1), None);
+        let err = cmd("false", Vec::<&str>::new()).run().unwrap_err();
+        assert_eq!(retry.delay(&err, 1), Some(Duration::from_millis(500)));
+        assert_eq!(retry.delay(&err, 3), Some(Duration::from_secs(2)));
+        assert_eq!(retry.delay(&err, 10), Some(MAX_RETRY_DELAY));
+        assert_eq!(retry.delay(&err, 11), None);
+
+        // Large backoffs are capped instead of overflowing
+        let retry = Retry {
+            retries: 40,
+            backoff: Duration::from_secs(u64::MAX / 4),
+            predicate: None,
+        };
+        assert_eq!(retry.delay(&err, 40), Some(MAX_RETRY_DELAY));
+    }
+
+    #[cfg(feature = "process_async")]
+    #[tokio::test]
+    async fn test_retry_async() {
+        let tmp = tempfile::tempdir().unwrap();
+        let output = flaky(tmp.path(), 2, 1)
+            .retry(2, Duration::from_millis(1))
+            .stderr_null()
+            .read_async()
+            .await
+            .unwrap();
+        assert_eq!(output, "done");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output = flaky(tmp.path(), 2, 1)
+            .retry(2, Duration::from_millis(1))
+            .retry_if(|failure| failure.stderr.contains("failed"))
+            .read_async()
+            .await
+            .unwrap();
+        assert_eq!(output, "done");
+    }
+}
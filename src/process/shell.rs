@@ -0,0 +1,262 @@
+//! Shell scripts with safely interpolated arguments
+//!
+//! [`sh!`](crate::sh) and [`sh_args`] run a script template in which each `{}` is
+//! replaced by the next argument, quoted for the shell, so paths with spaces or
+//! untrusted input can't change what the script does. Use `{{` and `}}` for literal
+//! braces, and don't put placeholders inside quotes in the template; the quoting is
+//! added for you.
+//!
+//! [`Shell`] picks the shell (`sh`, `bash`, `zsh` or `pwsh`); scripts run through
+//! [`XXExpression`], so failures are the same
+//! [`XXError::ProcessFailed`](crate::XXError::ProcessFailed) errors.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use xx::process::{self, Shell};
+//!
+//! # fn main() -> xx::XXResult<()> {
+//! let dir = "my documents";
+//! let files = xx::sh!("ls {} | wc -l", dir)?;
+//! let branch = Shell::Bash
+//!     .cmd("git -C {} rev-parse --abbrev-ref HEAD", [dir])?
+//!     .read()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsStr;
+
+use super::{ShellStyle, XXExpression, cmd};
+use crate::{XXResult, error};
+
+/// A shell to run scripts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Sh,
+    Bash,
+    Zsh,
+    /// PowerShell 7+
+    Pwsh,
+}
+
+impl Shell {
+    /// How arguments are quoted for this shell
+    pub fn style(self) -> ShellStyle {
+        match self {
+            Self::Sh | Self::Bash | Self::Zsh => ShellStyle::Posix,
+            Self::Pwsh => ShellStyle::PowerShell,
+        }
+    }
+
+    /// Interpolate `args` into `template`, quoting each one for this shell
+    ///
+    /// Fails if the number of `{}` placeholders doesn't match the number of arguments,
+    /// if a brace isn't escaped, or if an argument isn't valid UTF-8.
+    ///
+    /// # Example
+    /// ```
+    /// use xx::process::Shell;
+    /// assert_eq!(
+    ///     Shell::Sh.interpolate("cp {} {}", ["a b", "it's"]).unwrap(),
+    ///     r"cp 'a b' 'it'\''s'"
+    /// );
+    /// assert_eq!(
+    ///     Shell::Pwsh.interpolate("Get-Item {}", ["a b"]).unwrap(),
+    ///     "Get-Item 'a b'"
+    /// );
+    /// ```
+    pub fn interpolate<I, S>(self, template: &str, args: I) -> XXResult<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut args = args.into_iter();
+        let mut script = String::with_capacity(template.len());
+        let mut used = 0;
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    script.push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    let Some(arg) = args.next() else {
+                        return Err(error!(
+                            "not enough arguments for script template: {template}"
+                        ));
+                    };
+                    used += 1;
+                    // Replacing invalid bytes would turn the argument into a different one
+                    let Some(arg) = arg.as_ref().to_str() else {
+                        return Err(error!(
+                            "script argument {used} is not valid UTF-8: {}",
+                            arg.as_ref().display()
+                        ));
+                    };
+                    script.push_str(&self.style().quote(arg));
+                }
+                ('{', _) | ('}', _) => {
+                    return Err(error!(
+                        "unescaped '{c}' in script template (use {{}} for arguments or double it): {template}"
+                    ));
+                }
+                _ => script.push(c),
+            }
+        }
+        let extra = args.count();
+        if extra > 0 {
+            return Err(error!(
+                "script template has {used} placeholders but was given {} arguments: {template}",
+                used + extra
+            ));
+        }
+        Ok(script)
+    }
+
+    /// An [`XXExpression`] that runs `template` with `args` interpolated
+    ///
+    /// Configure it further (environment, capture, timeouts, ...) before running it.
+    pub fn cmd<I, S>(self, template: &str, args: I) -> XXResult<XXExpression>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let script = self.interpolate(template, args)?;
+        Ok(match self {
+            Self::Sh => cmd("sh", ["-c", &script]),
+            Self::Bash => cmd("bash", ["-c", &script]),
+            Self::Zsh => cmd("zsh", ["-c", &script]),
+            Self::Pwsh => cmd(
+                "pwsh",
+                ["-NoProfile", "-NonInteractive", "-Command", &script],
+            ),
+        })
+    }
+
+    /// Run `template` with `args` interpolated and return its stdout, with trailing
+    /// newlines trimmed
+    pub fn read<I, S>(self, template: &str, args: I) -> XXResult<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.cmd(template, args)?.read()
+    }
+}
+
+/// Run a `sh` script with `args` interpolated and return its stdout, with trailing
+/// newlines trimmed
+///
+/// See [`Shell::interpolate`] for the template syntax, and [`sh!`](crate::sh) for a
+/// shorthand.
+///
+/// # Example
+/// ```
+/// use xx::process;
+/// let output = process::sh_args("printf '%s\n' {}", ["$HOME; rm -rf /"]).unwrap();
+/// assert_eq!(output, "$HOME; rm -rf /");
+/// ```
+pub fn sh_args<I, S>(template: &str, args: I) -> XXResult<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Shell::Sh.read(template, args)
+}
+
+/// Run a shell script with safely quoted arguments and return its stdout
+///
+/// Each `{}` in the template is replaced by the next argument, which can be anything
+/// that is `AsRef<OsStr>` (strings, paths, ...) and valid UTF-8. Prefix the template with
+/// `shell = ...,` to use a shell other than `sh`. Fails with
+/// [`XXError::ProcessFailed`](crate::XXError::ProcessFailed) like
+/// [`XXExpression::read`](crate::process::XXExpression::read).
+///
+/// ## Example
+///
+/// ```rust
+/// use std::path::Path;
+/// use xx::process::Shell;
+///
+/// let dir = Path::new("/tmp/with space");
+/// let output = xx::sh!("echo {} {}", dir, "$(whoami)").unwrap();
+/// assert_eq!(output, "/tmp/with space $(whoami)");
+///
+/// let output = xx::sh!(shell = Shell::Bash, "[[ {} == *space ]] && echo yes", dir).unwrap();
+/// assert_eq!(output, "yes");
+/// ```
+#[macro_export]
+macro_rules! sh {
+    (shell = $shell:expr, $template:expr $(, $arg:expr)* $(,)?) => {
+        $shell.read(
+            $template,
+            &[$(::std::ffi::OsStr::new(&$arg)),*] as &[&::std::ffi::OsStr],
+        )
+    };
+    ($template:expr $(, $arg:expr)* $(,)?) => {
+        $crate::sh!(shell = $crate::process::Shell::Sh, $template $(, $arg)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::XXError;
+
+    #[test]
+    fn test_interpolate() {
+        let script = Shell::Sh
+            .interpolate("f() {{ echo $1; }}; f {}", ["a'b c"])
+            .unwrap();
+        assert_eq!(script, r"f() { echo $1; }; f 'a'\''b c'");
+
+        let err = Shell::Sh.interpolate("echo {} {}", ["a"]).unwrap_err();
+        assert!(err.to_string().contains("not enough arguments"));
+        let err = Shell::Sh.interpolate("echo {}", ["a", "b"]).unwrap_err();
+        assert!(err.to_string().contains("1 placeholders but was given 2"));
+        let err = Shell::Sh.interpolate("echo {0}", ["a"]).unwrap_err();
+        assert!(err.to_string().contains("unescaped '{'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_interpolate_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let arg = OsStr::from_bytes(b"file\xff.txt");
+        let err = Shell::Sh.interpolate("cat {}", [arg]).unwrap_err();
+        assert!(err.to_string().contains("argument 1 is not valid UTF-8"));
+    }
+
+    #[test]
+    fn test_sh_macro() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("a file; touch injected");
+        crate::sh!("echo hi > {}", &path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi\n");
+        assert!(!tmp.path().join("injected").exists());
+
+        let output = crate::sh!("echo ok",).unwrap();
+        assert_eq!(output, "ok");
+        let output = crate::sh!(shell = Shell::Sh, "printf %s {}", "`id`").unwrap();
+        assert_eq!(output, "`id`");
+    }
+
+    #[test]
+    fn test_sh_args_failure() {
+        let err = sh_args("echo {} >&2; exit 3", ["bad input"]).unwrap_err();
+        let XXError::ProcessFailed(failure) = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(failure.code, Some(3));
+        assert_eq!(
+            failure.command,
+            r"sh -c 'echo '\''bad input'\'' >&2; exit 3'"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Timeouts and cancellation for running processes
+//!
+//! When an [`XXExpression`](super::XXExpression) has a timeout or a [`CancelHandle`],
+//! the child is started in its own process group so that it can be stopped along with
+//! everything it spawned. Stopping sends `SIGTERM` to the group, waits for the grace
+//! period, then sends `SIGKILL`. On Windows the process tree is stopped with
+//! `taskkill`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::XXError;
+
+/// Default time between asking a process to stop and killing it
+pub const DEFAULT_KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How often a [`CancelHandle`] is checked while a process runs
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Stops running processes from another thread
+///
+/// Clones share the same state: cancelling any clone stops every process it was
+/// passed to with [`XXExpression::cancel_handle`](super::XXExpression::cancel_handle).
+/// A cancelled handle stays cancelled, so processes started with it afterwards are
+/// stopped immediately.
+///
+/// # Example
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use xx::process::{self, CancelHandle};
+///
+/// let cancel = CancelHandle::new();
+/// let watcher = cancel.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_secs(1));
+///     watcher.cancel();
+/// });
+/// let result = process::cmd("sleep", ["60"]).cancel_handle(&cancel).run();
+/// assert!(matches!(result, Err(xx::XXError::ProcessCancelled(_))));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// Create a handle that hasn't been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the processes using this handle
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Timeout and cancellation settings for a process
+#[derive(Clone)]
+pub(crate) struct Limits {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel: Option<CancelHandle>,
+    pub(crate) grace_period: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            cancel: None,
+            grace_period: DEFAULT_KILL_GRACE_PERIOD,
+        }
+    }
+}
+
+/// Why a process was stopped
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stop {
+    Timeout(Duration),
+    Cancelled,
+}
+
+impl Stop {
+    pub(crate) fn into_error(self, cmd: String) -> XXError {
+        match self {
+            Stop::Timeout(timeout) => XXError::ProcessTimeout(cmd, timeout),
+            Stop::Cancelled => XXError::ProcessCancelled(cmd),
+        }
+    }
+}
+
+impl Limits {
+    /// Whether the process needs to be supervised (and started in its own group)
+    pub(crate) fn is_active(&self) -> bool {
+        self.timeout.is_some() || self.cancel.is_some()
+    }
+
+    /// Start a thread that stops the process group led by `pid` if a limit is hit
+    pub(crate) fn watch(&self, pid: u32) -> Option<Watchdog> {
+        if !self.is_active() {
+            return None;
+        }
+        let exited = Arc::new((Mutex::new(false), Condvar::new()));
+        let limits = self.clone();
+        let state = exited.clone();
+        let thread = thread::spawn(move || limits.supervise(pid, &state));
+        Some(Watchdog {
+            exited,
+            thread: Some(thread),
+        })
+    }
+
+    fn supervise(&self, pid: u32, state: &(Mutex<bool>, Condvar)) -> Option<Stop> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let (lock, condvar) = state;
+        let mut exited = lock.lock().unwrap();
+        let stop = loop {
+            if *exited {
+                return None;
+            }
+            if let Some(stop) = self.check(deadline) {
+                break stop;
+            }
+            let wait = deadline.map_or(POLL_INTERVAL, |d| {
+                d.saturating_duration_since(Instant::now())
+                    .min(POLL_INTERVAL)
+            });
+            exited = condvar.wait_timeout(exited, wait).unwrap().0;
+        };
+        debug!("stopping process {pid}: {stop:?}");
+        signal_group(pid, false);
+        let (exited, _) = condvar
+            .wait_timeout_while(exited, self.grace_period, |exited| !*exited)
+            .unwrap();
+        drop(exited);
+        // Kill anything left in the group, even if the leader already exited
+        signal_group(pid, true);
+        Some(stop)
+    }
+
+    fn check(&self, deadline: Option<Instant>) -> Option<Stop> {
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Some(Stop::Cancelled);
+        }
+        match (deadline, self.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                Some(Stop::Timeout(timeout))
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolve when a limit is hit, for use with `tokio::select!`
+    #[cfg(feature = "process_async")]
+    pub(crate) async fn exceeded(&self) -> Stop {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(stop) = self.check(deadline) {
+                return stop;
+            }
+            let wait = deadline.map_or(POLL_INTERVAL, |d| {
+                d.saturating_duration_since(Instant::now())
+                    .min(POLL_INTERVAL)
+            });
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Supervises a running process; see [`Limits::watch`]
+pub(crate) struct Watchdog {
+    exited: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<thread::JoinHandle<Option<Stop>>>,
+}
+
+impl Watchdog {
+    /// Tell the watchdog the process has exited and return why it was stopped, if it was
+    ///
+    /// Call this once the child has been waited on and its output pipes are closed.
+    pub(crate) fn finish(mut self) -> Option<Stop> {
+        self.set_exited();
+        self.thread.take()?.join().ok().flatten()
+    }
+
+    fn set_exited(&self) {
+        let (lock, condvar) = &*self.exited;
+        *lock.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        // Don't signal a process group that may have been reused after an early return
+        self.set_exited();
+    }
+}
+
+/// Ask (or with `force`, make) every process in the group led by `pid` to exit
+pub(crate) fn signal_group(pid: u32, force: bool) {
+    #[cfg(all(unix, feature = "process_unix"))]
+    {
+        let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+        // SAFETY: kill has no memory safety requirements; a negative pid targets the group
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), signal);
+        }
+    }
+    #[cfg(all(unix, not(feature = "process_unix")))]
+    kill_command(&format!("-{pid}"), force);
+    #[cfg(not(unix))]
+    {
+        let pid = pid.to_string();
+        let mut args = vec!["/T", "/PID", pid.as_str()];
+        if force {
+            args.insert(0, "/F");
+        }
+        let _ = std::process::Command::new("taskkill")
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+/// Send `SIGTERM` (or with `force`, `SIGKILL`) to `target` with the `kill` command,
+/// when libc isn't available; a negative target is a process group
+#[cfg(all(unix, not(feature = "process_unix")))]
+pub(crate) fn kill_command(target: &str, force: bool) {
+    let signal = if force { "KILL" } else { "TERM" };
+    let _ = std::process::Command::new("kill")
+        .args(["-s", signal, "--", target])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+}
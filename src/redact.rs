@@ -0,0 +1,54 @@
+//! Recognizing secrets so they can be masked in displayed and stored values
+
+/// Shown in place of a secret
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Whether a header, query parameter or environment variable named `name` holds a
+/// secret
+///
+/// Case is ignored and `_` matches `-`, so `GITHUB_TOKEN`, `X-Api-Key` and `api_key`
+/// are all sensitive.
+pub(crate) fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase().replace('_', "-");
+    matches!(
+        name.as_str(),
+        "authorization" | "proxy-authorization" | "cookie" | "set-cookie"
+    ) || [
+        "token",
+        "secret",
+        "password",
+        "passwd",
+        "api-key",
+        "apikey",
+        "signature",
+        "credential",
+        "private-key",
+    ]
+    .iter()
+    .any(|s| name.contains(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sensitive() {
+        for name in [
+            "Authorization",
+            "cookie",
+            "X-Api-Key",
+            "private-token",
+            "GITHUB_TOKEN",
+            "db_password",
+            "API_KEY",
+            "AWS_SIGNATURE",
+            "SSH_PRIVATE_KEY",
+        ] {
+            assert!(is_sensitive(name), "{name}");
+        }
+        for name in ["PATH", "x-request-id", "page", "cookies-enabled"] {
+            assert!(!is_sensitive(name), "{name}");
+        }
+    }
+}